@@ -2,6 +2,15 @@
 
 echo "Installing NVM (Node Version Manager)..."
 
+# 用户模式: 不需要sudo, 版本安装在 $XDG_DATA_HOME/nvm-rs 下, 配置在 $XDG_CONFIG_HOME/nvm-rs 下
+if [ "$1" = "--user" ]; then
+    mkdir -p "$HOME/.local/bin"
+    cp ./nvm "$HOME/.local/bin/nvm"
+    "$HOME/.local/bin/nvm" init --shell bash
+    echo "NVM has been installed for $USER in user mode."
+    exit 0
+fi
+
 # 设置NVM_HOME的默认值
 NVM_HOME="$HOME/.nvm"
 
@@ -23,19 +32,15 @@ fi
 echo "NVM will be installed in: $NVM_HOME"
 echo "NVM will be symlinked to: $NVM_SYMLINK"
 
-# 判断是否存在，先删除再创建
-if [ -d "$NVM_HOME" ]; then
-    sudo rm -rf "$NVM_HOME"
-fi
 sudo mkdir -p "$NVM_HOME"
+sudo chown "$USER" "$NVM_HOME"
 
 # 安装NVM
 # 1、移动nvm-linux到NVM_HOME目录
-sudo cp ./nvm "$NVM_HOME"/nvm
+cp ./nvm "$NVM_HOME"/nvm
 
-# 创建settings文件,写入NVM_HOME(root)和NVM_SYMLINK(path)
-echo "root: $NVM_HOME" | sudo tee "$NVM_HOME/settings.txt"
-echo "symlink: $NVM_SYMLINK" | sudo tee -a "$NVM_HOME/settings.txt"
+# 创建settings文件、目录, 并在shell配置文件中写入PATH, 重复执行不会重复写入
+"$NVM_HOME"/nvm init --system --root "$NVM_HOME" --symlink "$NVM_SYMLINK" --shell bash
 
 # 确保 nvm 可以以管理员身份运行
 # 判断/usr/local/bin/nvm 是否存在，存在则删除
@@ -44,13 +49,7 @@ if [ ! -L "/usr/local/bin/nvm" ]; then
 fi
 
 # 给予卸载脚本可执行权限，并移动到NVM_HOME目录
-sudo cp ./uninstall.sh "$NVM_HOME"/nvm-uninstall.sh
-sudo chmod +x "$NVM_HOME"/nvm-uninstall.sh
-
-# 导出环境变量到用户目录
-echo "export NVM_HOME=$NVM_HOME" >> "$HOME/.bashrc"
-echo "export NVM_SYMLINK=$NVM_SYMLINK" >> "$HOME/.bashrc"
-echo "export PATH=$PATH:$NVM_HOME:$NVM_SYMLINK/bin" >> "$HOME/.bashrc"
-source "$HOME/.bashrc"
+cp ./uninstall.sh "$NVM_HOME"/nvm-uninstall.sh
+chmod +x "$NVM_HOME"/nvm-uninstall.sh
 
 echo "NVM has been installed successfully."
@@ -2,26 +2,27 @@
 
 echo "Uninstalling NVM (Node Version Manager)..."
 
-# 通过环境变量判断是否安装NVM
+# 通过环境变量或PATH中的nvm判断是否安装NVM
 if [ -z "$NVM_HOME" ]; then
-    echo "NVM is not installed."
-    exit 1
+    NVM_BIN=$(command -v nvm)
+    if [ -z "$NVM_BIN" ]; then
+        echo "NVM is not installed."
+        exit 1
+    fi
+    NVM_HOME=$(dirname "$(readlink -f "$NVM_BIN")")
 fi
 
+# 删除已安装的版本、下载目录、软链接、配置以及shell配置文件中的nvm内容
+"$NVM_HOME"/nvm implode "$@" || exit 1
+
 # 删除/usr/local/bin/nvm 软链接
 if [ -L "/usr/local/bin/nvm" ]; then
     sudo rm -f /usr/local/bin/nvm
 fi
 
-# 删除NVM_SYMLINK软链接
-if [ -L "$NVM_SYMLINK" ]; then
-    sudo rm -f "$NVM_SYMLINK"
-fi
-
-# 删除NVM_HOME目录
-if [ -d "$NVM_HOME" ]; then
-    sudo rm -rf "$NVM_HOME"
-fi
+# 删除nvm可执行文件, 保留了版本时NVM_HOME目录不删除
+rm -f "$NVM_HOME"/nvm "$NVM_HOME"/nvm-uninstall.sh "$NVM_HOME"/nvm-uninstall.zsh
+rmdir "$NVM_HOME" 2>/dev/null
 
 echo "NVM has been uninstalled successfully."
-echo "Please manually remove the environment variables NVM_HOME and NVM_SYMLINK.!!!"
\ No newline at end of file
+echo "Open a new shell to drop NVM_HOME and NVM_SYMLINK from the environment."
\ No newline at end of file
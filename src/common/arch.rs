@@ -1,91 +1,199 @@
+use goblin::container::Endian;
+use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_PPC64, EM_S390, EM_X86_64};
+use goblin::mach::cputype::{CPU_TYPE_ARM, CPU_TYPE_ARM64, CPU_TYPE_X86, CPU_TYPE_X86_64};
 use goblin::mach::Mach;
+use goblin::pe::header::{
+    COFF_MACHINE_ARM64, COFF_MACHINE_ARMNT, COFF_MACHINE_X86, COFF_MACHINE_X86_64,
+};
 use goblin::Object;
 
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fmt::{self, Display, Formatter};
 
-// get the bit of the file
-pub fn bit(path: &str) -> &str {
-    let res = File::open(path);
-    if res.is_err() {
-        println!("Open file fail, err:{:?}", res.as_ref().err());
-        return "?";
-    }
-    let mut file = res.unwrap();
-    let mut buffer = Vec::new();
-    if let Err(err) = file.read_to_end(&mut buffer) {
-        println!("Read File content fail, err:{:?}", err);
-        return "?";
+/// A CPU architecture node.js is built for, named the way nodejs.org names
+/// its downloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X64,
+    X86,
+    Arm64,
+    Armv7l,
+    Ppc64le,
+    S390x,
+}
+
+impl Arch {
+    pub const ALL: [Arch; 6] = [
+        Arch::X64,
+        Arch::X86,
+        Arch::Arm64,
+        Arch::Armv7l,
+        Arch::Ppc64le,
+        Arch::S390x,
+    ];
+
+    /// The architecture nvm itself was built for.
+    pub fn host() -> Arch {
+        // Rust calls both byte orders powerpc64, node is only built for the
+        // little endian one
+        if env::consts::ARCH == "powerpc64" && cfg!(target_endian = "little") {
+            return Arch::Ppc64le;
+        }
+        Arch::parse(env::consts::ARCH).unwrap_or(Arch::X64)
     }
 
-    let res = Object::parse(&buffer);
-    if res.is_err() {
-        println!("File header parse fail, err:{:?}", res.as_ref().err());
-        return "?";
+    /// Node style (x64, arm64, armv7l) or Rust style (x86_64, aarch64) names.
+    /// 32 and 64 pick the build of that width for the host's processor family.
+    pub fn parse(name: &str) -> Option<Arch> {
+        let arch = match name.trim().to_ascii_lowercase().as_str() {
+            "x64" | "x86_64" | "amd64" => Arch::X64,
+            "x86" | "ia32" | "i386" | "i686" => Arch::X86,
+            "arm64" | "aarch64" => Arch::Arm64,
+            "armv7l" | "armv7" | "arm" => Arch::Armv7l,
+            "ppc64le" | "powerpc64le" => Arch::Ppc64le,
+            "s390x" => Arch::S390x,
+            "64" => match Arch::host() {
+                Arch::X86 => Arch::X64,
+                Arch::Armv7l => Arch::Arm64,
+                host => host,
+            },
+            "32" => match Arch::host() {
+                Arch::Arm64 | Arch::Armv7l => Arch::Armv7l,
+                _ => Arch::X86,
+            },
+            _ => return None,
+        };
+        Some(arch)
     }
 
-    let obj = res.unwrap();
-    match obj {
-        Object::Elf(elf) => {
-            return if elf.is_64 { "64" } else { "32" };
+    /// The name used in node.js download file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Arch::X64 => "x64",
+            Arch::X86 => "x86",
+            Arch::Arm64 => "arm64",
+            Arch::Armv7l => "armv7l",
+            Arch::Ppc64le => "ppc64le",
+            Arch::S390x => "s390x",
         }
-        Object::PE(pe) => {
-            return if pe.is_64 { "64" } else { "32" };
-        }
-        Object::Mach(mach) => match mach {
-            Mach::Binary(b) => {
-                return if b.is_64 { "64" } else { "32" };
-            }
-            _ => {
-                println!("Not support file format");
-                return "?";
-            }
-        },
-        _ => {
-            println!("Unknown file format");
-            return "?";
+    }
+
+    /// "32" or "64", the suffix of the side by side node32/node64 executables.
+    pub fn bits(&self) -> &'static str {
+        match self {
+            Arch::X86 | Arch::Armv7l => "32",
+            _ => "64",
         }
     }
+
+    /// The names accepted by [`Arch::parse`], for error messages.
+    pub fn names() -> String {
+        let names: Vec<&str> = Arch::ALL.iter().map(|a| a.name()).collect();
+        names.join(", ")
+    }
 }
 
-// get the current arch
-pub fn arch_map() -> String {
-    let a = env::consts::ARCH;
+impl Display for Arch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
-    let node_arch = match a {
-        "x86" => "x86",
-        "x86_64" => "x64",
-        "arm" => "armv6",
-        "armv7" => "armv7",
-        "aarch64" => "arm64",
-        _ => {
-            println!("Not support this arch: {}", a);
-            "?"
-        }
-    };
-    return node_arch.to_string();
+/// The architecture an executable was built for, None when it can not be
+/// read or is a universal binary.
+pub fn of(path: &str) -> Option<Arch> {
+    let buffer = std::fs::read(path).ok()?;
+    match Object::parse(&buffer).ok()? {
+        Object::Elf(elf) => match elf.header.e_machine {
+            EM_X86_64 => Some(Arch::X64),
+            EM_386 => Some(Arch::X86),
+            EM_AARCH64 => Some(Arch::Arm64),
+            EM_ARM => Some(Arch::Armv7l),
+            EM_PPC64 if elf.header.endianness().is_ok_and(|e| e == Endian::Little) => {
+                Some(Arch::Ppc64le)
+            }
+            EM_S390 => Some(Arch::S390x),
+            _ => None,
+        },
+        Object::PE(pe) => match pe.header.coff_header.machine {
+            COFF_MACHINE_X86_64 => Some(Arch::X64),
+            COFF_MACHINE_X86 => Some(Arch::X86),
+            COFF_MACHINE_ARM64 => Some(Arch::Arm64),
+            COFF_MACHINE_ARMNT => Some(Arch::Armv7l),
+            _ => None,
+        },
+        Object::Mach(Mach::Binary(b)) => match b.header.cputype {
+            CPU_TYPE_X86_64 => Some(Arch::X64),
+            CPU_TYPE_X86 => Some(Arch::X86),
+            CPU_TYPE_ARM64 => Some(Arch::Arm64),
+            CPU_TYPE_ARM => Some(Arch::Armv7l),
+            _ => None,
+        },
+        _ => None,
+    }
 }
 
-// validate the input arch, if it is empty, return the current arch
-pub fn validate(str: &str) -> String {
-    let mut tmp = str.to_string();
-    if str.is_empty() {
-        tmp = std::env::consts::ARCH.to_string();
+/// The C library of the host, "musl" on Alpine and other musl based
+/// distributions, "glibc" everywhere else.
+pub fn libc() -> &'static str {
+    // the loader a dynamically linked system binary asks for names the libc
+    for path in ["/bin/sh", "/usr/bin/env"] {
+        if let Ok(buffer) = std::fs::read(path) {
+            if let Ok(Object::Elf(elf)) = Object::parse(&buffer) {
+                if let Some(interpreter) = elf.interpreter {
+                    return if interpreter.contains("musl") {
+                        "musl"
+                    } else {
+                        "glibc"
+                    };
+                }
+            }
+        }
     }
-    if tmp.contains("64") {
-        "64".to_string()
+    let musl_loader = std::fs::read_dir("/lib").is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|e| e.file_name().to_string_lossy().starts_with("ld-musl-"))
+    });
+    if musl_loader {
+        "musl"
     } else {
-        "32".to_string()
+        "glibc"
     }
 }
 
 #[cfg(test)]
 #[test]
-#[cfg(target_os = "windows")]
-fn test_validate() {
-    let res = validate("");
-    assert_eq!(res, "64");
+#[cfg(target_os = "linux")]
+fn test_libc() {
+    let musl = std::path::Path::new("/etc/alpine-release").exists();
+    assert_eq!(libc(), if musl { "musl" } else { "glibc" });
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse() {
+    assert_eq!(Arch::parse("x86_64"), Some(Arch::X64));
+    assert_eq!(Arch::parse("aarch64"), Some(Arch::Arm64));
+    assert_eq!(Arch::parse("armv7l"), Some(Arch::Armv7l));
+    assert_eq!(Arch::parse("s390x").map(|a| a.bits()), Some("64"));
+    assert_eq!(Arch::parse("64").map(|a| a.bits()), Some("64"));
+    assert_eq!(Arch::parse("32").map(|a| a.bits()), Some("32"));
+    assert_eq!(Arch::parse("mips"), None);
+    // big endian ppc64 has no node builds
+    assert_eq!(Arch::parse("powerpc64"), None);
+    for arch in Arch::ALL {
+        assert_eq!(Arch::parse(arch.name()), Some(arch));
+    }
+}
+
+#[cfg(test)]
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn test_of() {
+    // the test binary is built for the host
+    let exe = env::current_exe().unwrap();
+    assert_eq!(of(&exe.to_string_lossy()), Some(Arch::host()));
 }
 
 #[cfg(test)]
@@ -93,6 +201,5 @@ fn test_validate() {
 #[cfg(target_os = "windows")]
 fn test_bit() {
     let path = "assets/64bit.exe";
-    let res = bit(path);
-    assert_eq!(res, "64");
+    assert_eq!(of(path).map(|a| a.bits()), Some("64"));
 }
@@ -0,0 +1,75 @@
+use crate::common::error::{NvmError, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+/// Hex sha256 of a file.
+pub fn sha256(path: &str) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The sum listed for `name` in a SHASUMS256.txt, lines are `<sum>  <name>`.
+pub fn find(shasums: &str, name: &str) -> Option<String> {
+    shasums.lines().find_map(|line| {
+        let (sum, file) = line.trim().split_once(char::is_whitespace)?;
+        let file = file.trim_start().trim_start_matches('*');
+        (file == name).then(|| sum.to_ascii_lowercase())
+    })
+}
+
+/// Check a file against its entry in the SHASUMS256.txt at `shasums_path`.
+pub fn verify(path: &str, shasums_path: &str) -> Result<()> {
+    let shasums = fs::read_to_string(shasums_path)
+        .map_err(|err| NvmError::io(&format!("Read {}", shasums_path), err))?;
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let expected = find(&shasums, &name)
+        .ok_or_else(|| NvmError::Checksum(format!("{} is not listed in {}", name, shasums_path)))?;
+    let actual = sha256(path).map_err(|err| NvmError::io(&format!("Read {}", path), err))?;
+    if actual != expected {
+        return Err(NvmError::Checksum(format!(
+            "{} does not match {}, expected sha256 {} but got {}",
+            path, shasums_path, expected, actual
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn test_verify() {
+    let dir = std::env::temp_dir().join(format!("nvm-checksum-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let archive = dir.join("node-v20.11.0-linux-x64.tar.gz");
+    fs::write(&archive, "node").unwrap();
+    let archive = archive.to_string_lossy().to_string();
+    let shasums = dir.join("SHASUMS256.txt").to_string_lossy().to_string();
+
+    let sum = "2bba0f4ff1c7fdb6a8bb2bd5c5a8ef6dac4fd8f3a1e86cc3a3ec8bca5e0e1f9e";
+    fs::write(
+        &shasums,
+        format!("{}  node-v20.11.0-linux-x64.tar.gz\n", sum),
+    )
+    .unwrap();
+    assert_eq!(verify(&archive, &shasums).unwrap_err().exit_code(), 5);
+
+    let sum = sha256(&archive).unwrap();
+    fs::write(
+        &shasums,
+        format!(
+            "{}  node-v20.11.0-linux-x64.tar.xz\n{}  node-v20.11.0-linux-x64.tar.gz\n",
+            "0".repeat(64),
+            sum
+        ),
+    )
+    .unwrap();
+    verify(&archive, &shasums).unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,5 +1,9 @@
-use std::process::Command;
+use std::fs::File;
+use std::io;
+use std::process::{Command, ExitStatus};
 
+// cmd builtins such as rmdir and mklink, elevated when they are refused
+#[cfg(target_os = "windows")]
 pub fn elevated_run(root: &str, name: &str, args: Vec<&str>) -> Result<bool, String> {
     let mut cmd_arg = args.clone();
     cmd_arg.insert(0, "/C");
@@ -14,6 +18,7 @@ pub fn elevated_run(root: &str, name: &str, args: Vec<&str>) -> Result<bool, Str
     }
 }
 
+#[cfg(target_os = "windows")]
 fn run(name: &str, dir: Option<&str>, args: &Vec<&str>) -> Result<bool, String> {
     let mut cmd = Command::new(name);
 
@@ -35,3 +40,15 @@ fn run(name: &str, dir: Option<&str>, args: &Vec<&str>) -> Result<bool, String>
         Err(e) => Err(format!("Failed to execute command: {}", e)),
     }
 }
+
+/// Run a command in `dir` with its stdout and stderr appended to `log`.
+pub fn run_logged(name: &str, dir: &str, args: &[&str], log: &str) -> io::Result<ExitStatus> {
+    let out = File::options().create(true).append(true).open(log)?;
+    let err = out.try_clone()?;
+    Command::new(name)
+        .current_dir(dir)
+        .args(args)
+        .stdout(out)
+        .stderr(err)
+        .status()
+}
@@ -0,0 +1,137 @@
+use std::fmt::{Display, Formatter};
+use std::{fmt, io};
+
+/// Every failure nvm reports. Each kind exits with its own code so scripts can
+/// tell them apart:
+///
+/// | code | kind       |
+/// |------|------------|
+/// | 1    | other      |
+/// | 2    | usage      |
+/// | 3    | config     |
+/// | 4    | network    |
+/// | 5    | checksum   |
+/// | 6    | not found  |
+/// | 7    | permission |
+/// | 8    | resolution |
+/// | 9    | busy       |
+/// | 10   | vulnerable |
+#[derive(Debug)]
+pub enum NvmError {
+    /// Anything that does not fit below, mostly local io failures
+    Other(String),
+    /// Bad command line arguments
+    Usage(String),
+    /// Missing or invalid settings
+    Config(String),
+    /// The mirror could not be reached or answered with an error
+    Network(String),
+    /// A download did not match its published checksum
+    Checksum(String),
+    /// A version, file or directory does not exist
+    NotFound(String),
+    /// Access to a file or link was denied
+    Permission(String),
+    /// A version or alias could not be resolved to a release
+    Resolution(String),
+    /// Another nvm process holds the lock on the root and --no-wait was given
+    Busy(String),
+    /// The version in use is missing security releases of its line
+    Vulnerable(String),
+}
+
+pub type Result<T> = std::result::Result<T, NvmError>;
+
+impl NvmError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NvmError::Other(_) => 1,
+            NvmError::Usage(_) => 2,
+            NvmError::Config(_) => 3,
+            NvmError::Network(_) => 4,
+            NvmError::Checksum(_) => 5,
+            NvmError::NotFound(_) => 6,
+            NvmError::Permission(_) => 7,
+            NvmError::Resolution(_) => 8,
+            NvmError::Busy(_) => 9,
+            NvmError::Vulnerable(_) => 10,
+        }
+    }
+
+    /// An io error with the path or action it happened on.
+    pub fn io(context: &str, err: io::Error) -> NvmError {
+        NvmError::from(err).context(context)
+    }
+
+    /// The same kind of error with what nvm was doing put in front of it.
+    pub fn context(self, context: &str) -> NvmError {
+        let wrap = |msg: String| format!("{}: {}", context, msg);
+        match self {
+            NvmError::Other(msg) => NvmError::Other(wrap(msg)),
+            NvmError::Usage(msg) => NvmError::Usage(wrap(msg)),
+            NvmError::Config(msg) => NvmError::Config(wrap(msg)),
+            NvmError::Network(msg) => NvmError::Network(wrap(msg)),
+            NvmError::Checksum(msg) => NvmError::Checksum(wrap(msg)),
+            NvmError::NotFound(msg) => NvmError::NotFound(wrap(msg)),
+            NvmError::Permission(msg) => NvmError::Permission(wrap(msg)),
+            NvmError::Resolution(msg) => NvmError::Resolution(wrap(msg)),
+            NvmError::Busy(msg) => NvmError::Busy(wrap(msg)),
+            NvmError::Vulnerable(msg) => NvmError::Vulnerable(wrap(msg)),
+        }
+    }
+}
+
+impl Display for NvmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NvmError::Other(msg)
+            | NvmError::Usage(msg)
+            | NvmError::Config(msg)
+            | NvmError::Network(msg)
+            | NvmError::Checksum(msg)
+            | NvmError::NotFound(msg)
+            | NvmError::Permission(msg)
+            | NvmError::Resolution(msg)
+            | NvmError::Busy(msg)
+            | NvmError::Vulnerable(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for NvmError {}
+
+impl From<io::Error> for NvmError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => NvmError::NotFound(err.to_string()),
+            io::ErrorKind::PermissionDenied => NvmError::Permission(err.to_string()),
+            _ => NvmError::Other(err.to_string()),
+        }
+    }
+}
+
+// errors from the settings code, keeping the kind of an underlying io error
+impl From<anyhow::Error> for NvmError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<io::Error>() {
+            Ok(err) => err.into(),
+            Err(err) => NvmError::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for NvmError {
+    fn from(err: reqwest::Error) -> Self {
+        NvmError::Network(err.to_string())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_exit_code() {
+    let err: NvmError = io::Error::new(io::ErrorKind::PermissionDenied, "denied").into();
+    assert_eq!(err.exit_code(), 7);
+    let err = NvmError::Resolution("no such release".to_string()).context("resolve v99");
+    assert_eq!(err.exit_code(), 8);
+    assert_eq!(err.to_string(), "resolve v99: no such release");
+}
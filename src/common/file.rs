@@ -1,31 +1,22 @@
 use std::fs::File;
-use std::io::{BufRead, Error};
+use std::io::Error;
 use std::path::Path;
 use std::{fs, io};
 
 use anyhow::Result;
 
-#[cfg(target_os = "linux")]
-use flate2::read::GzDecoder;
-#[cfg(target_os = "linux")]
-use std::env;
-#[cfg(target_os = "linux")]
-use std::os::unix::fs::PermissionsExt;
-#[cfg(target_os = "linux")]
-use std::path::PathBuf;
-#[cfg(target_os = "linux")]
-use tar::{Archive, EntryType};
-
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "windows"))]
 use flate2::read::GzDecoder;
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "windows"))]
 use std::env;
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "windows"))]
 use std::os::unix::fs::PermissionsExt;
-#[cfg(target_os = "macos")]
-use std::path::PathBuf;
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "windows"))]
+use std::path::{Component, PathBuf};
+#[cfg(not(target_os = "windows"))]
 use tar::{Archive, EntryType};
+#[cfg(not(target_os = "windows"))]
+use xz2::read::XzDecoder;
 
 #[cfg(target_os = "windows")]
 use zip;
@@ -65,37 +56,63 @@ pub fn unzip(zip_path: &str, dest: &str, tag: bool) -> Result<(), Error> {
     Ok(())
 }
 
-#[cfg(target_os = "linux")]
-pub fn untar(tar_gz_path: &str, dest: &str, tag: bool) -> Result<(), Error> {
-    let tar_gz = File::open(tar_gz_path)?;
-    let tar = GzDecoder::new(tar_gz);
-    let mut archive = Archive::new(tar);
-
-    for entry in archive.entries()? {
-        let mut entry = entry?;
-        let path = entry.path()?;
-        let filename = path.to_string_lossy();
-
-        let filepath = if tag {
-            format!(
-                "{}/{}",
-                dest,
-                filename.split('/').skip(1).collect::<Vec<_>>().join("/")
-            )
+/// Extract a .tar.gz or .tar.xz into dest, stripping the single top-level directory
+/// `top` every entry must sit under. Absolute paths, `..` components and
+/// links that point outside dest are refused, and the offending entries
+/// reported, before anything is written.
+#[cfg(not(target_os = "windows"))]
+pub fn untar(tar_path: &str, dest: &str, top: &str) -> Result<(), Error> {
+    let open = || -> Result<Archive<Box<dyn io::Read>>, Error> {
+        let file = File::open(tar_path)?;
+        let reader: Box<dyn io::Read> = if tar_path.ends_with(".xz") {
+            Box::new(XzDecoder::new(file))
         } else {
-            format!("{}/{}", dest, filename)
+            Box::new(GzDecoder::new(file))
         };
+        Ok(Archive::new(reader))
+    };
+
+    let mut offending = Vec::new();
+    for entry in open()?.entries()? {
+        let entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        if let Err(reason) = check_entry(&entry, &path, top) {
+            offending.push(format!("{} ({})", path.to_string_lossy(), reason));
+        }
+    }
+    if !offending.is_empty() {
+        let more = offending.len().saturating_sub(10);
+        offending.truncate(10);
+        if more > 0 {
+            offending.push(format!("and {} more", more));
+        }
+        return Err(Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has unsafe entries:\n  {}",
+                tar_path,
+                offending.join("\n  ")
+            ),
+        ));
+    }
+
+    fs::create_dir_all(dest)?;
+    let dest = fs::canonicalize(dest)?;
+    for entry in open()?.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if !extracted(entry_type) {
+            continue;
+        }
+        let filepath = dest.join(strip_top(&entry.path()?, top).unwrap_or_default());
 
-        match entry.header().entry_type() {
+        match entry_type {
             EntryType::Directory => {
                 fs::create_dir_all(&filepath)?;
             }
             EntryType::Regular => {
-                if let Some(parent) = Path::new(&filepath).parent() {
-                    if !parent.exists() {
-                        fs::create_dir_all(parent)?;
-                    }
-                }
+                parent_within(&dest, &filepath)?;
+                remove_link(&filepath)?;
                 let mut extracted_file = File::create(&filepath)?;
                 io::copy(&mut entry, &mut extracted_file)?;
 
@@ -104,99 +121,141 @@ pub fn untar(tar_gz_path: &str, dest: &str, tag: bool) -> Result<(), Error> {
                 fs::set_permissions(&filepath, fs::Permissions::from_mode(mode))?;
             }
             EntryType::Symlink => {
-                let target = entry.link_name()?;
-                if let Some(target) = target {
-                    std::os::unix::fs::symlink(target, &filepath)?;
+                let parent = parent_within(&dest, &filepath)?;
+                let target = entry.link_name()?.unwrap_or_default().to_path_buf();
+                // leading `..` are the only ones allowed, so counting them
+                // from the resolved parent tells where the link ends up
+                let depth = parent
+                    .strip_prefix(&dest)
+                    .map_or(0, |p| p.components().count());
+                if ups(&target) > depth {
+                    return Err(outside(&filepath, &dest));
                 }
+                remove_link(&filepath)?;
+                std::os::unix::fs::symlink(target, &filepath)?;
             }
             EntryType::Link => {
-                let target = entry.link_name()?;
-                if let Some(target) = target {
-                    fs::hard_link(target, &filepath)?;
-                }
-            }
-            _ => {
-                // Handle other entry types if necessary
+                parent_within(&dest, &filepath)?;
+                let target = entry.link_name()?.unwrap_or_default().to_path_buf();
+                let source = dest.join(strip_top(&target, top).unwrap_or_default());
+                parent_within(&dest, &source)?;
+                remove_link(&filepath)?;
+                fs::hard_link(source, &filepath)?;
             }
+            _ => {}
         }
     }
 
     Ok(())
 }
 
-#[cfg(target_os = "macos")]
-pub fn untar(tar_gz_path: &str, dest: &str, tag: bool) -> Result<(), Error> {
-    let tar_gz = File::open(tar_gz_path)?;
-    let tar = GzDecoder::new(tar_gz);
-    let mut archive = Archive::new(tar);
-
-    for entry in archive.entries()? {
-        let mut entry = entry?;
-        let path = entry.path()?;
-        let filename = path.to_string_lossy();
-
-        let filepath = if tag {
-            format!(
-                "{}/{}",
-                dest,
-                filename.split('/').skip(1).collect::<Vec<_>>().join("/")
-            )
-        } else {
-            format!("{}/{}", dest, filename)
-        };
-
-        match entry.header().entry_type() {
-            EntryType::Directory => {
-                fs::create_dir_all(&filepath)?;
-            }
-            EntryType::Regular => {
-                if let Some(parent) = Path::new(&filepath).parent() {
-                    if !parent.exists() {
-                        fs::create_dir_all(parent)?;
-                    }
-                }
-                let mut extracted_file = File::create(&filepath)?;
-                io::copy(&mut entry, &mut extracted_file)?;
+#[cfg(not(target_os = "windows"))]
+fn extracted(entry_type: EntryType) -> bool {
+    matches!(
+        entry_type,
+        EntryType::Directory | EntryType::Regular | EntryType::Symlink | EntryType::Link
+    )
+}
 
-                // Set file permissions
-                let mode = entry.header().mode()?;
-                fs::set_permissions(&filepath, fs::Permissions::from_mode(mode))?;
-            }
-            EntryType::Symlink => {
-                let target = entry.link_name()?;
-                if let Some(target) = target {
-                    std::os::unix::fs::symlink(target, &filepath)?;
-                }
-            }
-            EntryType::Link => {
-                let target = entry.link_name()?;
-                if let Some(target) = target {
-                    fs::hard_link(target, &filepath)?;
-                }
+// why an entry may not be extracted, checked on the archive alone
+#[cfg(not(target_os = "windows"))]
+fn check_entry<R: io::Read>(entry: &tar::Entry<R>, path: &Path, top: &str) -> Result<(), String> {
+    let entry_type = entry.header().entry_type();
+    if !extracted(entry_type) {
+        return Ok(());
+    }
+    let rel = strip_top(path, top)?;
+    let target = entry
+        .link_name()
+        .map_err(|err| err.to_string())?
+        .map(|t| t.to_path_buf());
+    match (entry_type, target) {
+        (EntryType::Symlink, Some(target)) => {
+            if target.has_root() {
+                return Err(format!("absolute link target {}", target.to_string_lossy()));
             }
-            _ => {
-                // Handle other entry types if necessary
+            let depth = rel.components().count().saturating_sub(1);
+            let inner = target.components().skip(ups(&target));
+            if ups(&target) > depth || inner.into_iter().any(|c| c == Component::ParentDir) {
+                return Err(format!(
+                    "link to {} leaves the archive",
+                    target.to_string_lossy()
+                ));
             }
+            Ok(())
         }
+        (EntryType::Link, Some(target)) => strip_top(&target, top)
+            .map(|_| ())
+            .map_err(|reason| format!("hard link target {}", reason)),
+        (EntryType::Symlink | EntryType::Link, None) => Err("link without a target".to_string()),
+        _ => Ok(()),
     }
+}
 
-    Ok(())
+// the path inside the top-level directory, which must be the first component
+#[cfg(not(target_os = "windows"))]
+fn strip_top(path: &Path, top: &str) -> Result<PathBuf, String> {
+    let mut components = path.components().filter(|c| *c != Component::CurDir);
+    match components.next() {
+        Some(Component::Normal(first)) if first == top => {}
+        Some(Component::RootDir | Component::Prefix(_)) => {
+            return Err("absolute path".to_string());
+        }
+        _ => return Err(format!("not under {}/", top)),
+    }
+    let mut rel = PathBuf::new();
+    for component in components {
+        match component {
+            Component::Normal(name) => rel.push(name),
+            Component::ParentDir => return Err("contains ..".to_string()),
+            _ => return Err("absolute path".to_string()),
+        }
+    }
+    Ok(rel)
 }
 
-pub fn read_lines(path: &str) -> Result<Vec<String>> {
-    let file = File::open(path)?;
-    let mut reader = io::BufReader::new(file);
+// number of leading `..` in a link target
+#[cfg(not(target_os = "windows"))]
+fn ups(target: &Path) -> usize {
+    target
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .take_while(|c| *c == Component::ParentDir)
+        .count()
+}
 
-    let mut res: Vec<String> = Vec::new();
-    loop {
-        let mut tmp = String::new();
-        let size = reader.read_line(&mut tmp)?;
-        if size <= 0 {
-            break;
-        }
-        res.push(tmp);
+// create the parent of an entry and make sure it did not end up outside
+// dest through a link extracted earlier
+#[cfg(not(target_os = "windows"))]
+fn parent_within(dest: &Path, filepath: &Path) -> Result<PathBuf, Error> {
+    let parent = filepath.parent().unwrap_or(dest);
+    fs::create_dir_all(parent)?;
+    let parent = fs::canonicalize(parent)?;
+    if !parent.starts_with(dest) {
+        return Err(outside(filepath, dest));
     }
-    Ok(res)
+    Ok(parent)
+}
+
+// a link already at the path would be written through, replace it instead
+#[cfg(not(target_os = "windows"))]
+fn remove_link(filepath: &Path) -> Result<(), Error> {
+    match fs::symlink_metadata(filepath) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::remove_file(filepath),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn outside(filepath: &Path, dest: &Path) -> Error {
+    Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} would be extracted outside {}",
+            filepath.to_string_lossy(),
+            dest.to_string_lossy()
+        ),
+    )
 }
 
 pub fn exists(path: &str) -> bool {
@@ -209,6 +268,29 @@ pub fn exists(path: &str) -> bool {
     }
 }
 
+/// Bytes used by the files under `path`, links are counted but not followed.
+pub fn dir_size(path: &Path) -> u64 {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return 0,
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    match fs::read_dir(path) {
+        Ok(entries) => entries.flatten().map(|e| dir_size(&e.path())).sum(),
+        Err(_) => 0,
+    }
+}
+
+/// Fails when entries can not be created and removed in `dir`, found out by
+/// doing so.
+pub fn check_writable(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".nvm-write-check-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
 #[cfg(target_os = "linux")]
 pub fn get_executable_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
     // 获取当前可执行文件的路径
@@ -279,3 +361,75 @@ fn test_get_executable_path() {
         println!("exe dir: {}", path.to_str().unwrap());
     }
 }
+
+#[cfg(test)]
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn test_untar() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    let dir = std::env::temp_dir().join(format!("nvm-untar-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let top = "node-v20.1.0-linux-x64";
+    // (path, link target), written raw so bad paths are not rejected while building
+    let build = |name: &str, entries: &[(&str, Option<&str>)]| {
+        let path = dir.join(name);
+        let file = File::create(&path).unwrap();
+        let encoder: Box<dyn io::Write> = if name.ends_with(".xz") {
+            Box::new(xz2::write::XzEncoder::new(file, 6))
+        } else {
+            Box::new(GzEncoder::new(file, Compression::default()))
+        };
+        let mut builder = tar::Builder::new(encoder);
+        for (entry, target) in entries {
+            let mut header = tar::Header::new_gnu();
+            let gnu = header.as_gnu_mut().unwrap();
+            gnu.name[..entry.len()].copy_from_slice(entry.as_bytes());
+            if let Some(target) = target {
+                gnu.linkname[..target.len()].copy_from_slice(target.as_bytes());
+                header.set_entry_type(EntryType::Symlink);
+            }
+            header.set_mode(0o755);
+            header.set_size(if target.is_some() { 0 } else { 4 });
+            header.set_cksum();
+            let data: &[u8] = if target.is_some() { b"" } else { b"node" };
+            builder.append(&header, data).unwrap();
+        }
+        drop(builder.into_inner().unwrap());
+        path.to_string_lossy().to_string()
+    };
+
+    for name in ["good.tar.gz", "good.tar.xz"] {
+        let good = build(
+            name,
+            &[
+                ("node-v20.1.0-linux-x64/lib/npm-cli.js", None),
+                ("node-v20.1.0-linux-x64/bin/npm", Some("../lib/npm-cli.js")),
+            ],
+        );
+        let out = dir.join(name).with_extension("out");
+        untar(&good, &out.to_string_lossy(), top).unwrap();
+        assert_eq!(fs::read_to_string(out.join("bin/npm")).unwrap(), "node");
+    }
+
+    let bad = build(
+        "bad.tar.gz",
+        &[
+            ("node-v20.1.0-linux-x64/../../evil", None),
+            ("other/file", None),
+            ("node-v20.1.0-linux-x64/bin/sh", Some("/bin/sh")),
+            ("node-v20.1.0-linux-x64/bin/up", Some("../../outside")),
+        ],
+    );
+    let out = dir.join("bad").to_string_lossy().to_string();
+    let err = untar(&bad, &out, top).unwrap_err().to_string();
+    for entry in ["../../evil", "other/file", "bin/sh", "bin/up"] {
+        assert!(err.contains(entry), "{} not reported in {}", entry, err);
+    }
+    assert!(!dir.join("bad").exists());
+    assert!(!dir.join("evil").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,137 @@
+use crate::common::arch::{self, Arch};
+use crate::common::error::{NvmError, Result};
+use crate::common::{filepath, link, lock};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Versions used to be extracted straight into {root}/v{version}, they are
+// now kept per architecture in {root}/v{version}/{arch} so that several
+// builds of one version can be installed side by side.
+
+/// Move versions installed with the old layout into their architecture
+/// dir, and point the symlink at the moved version if it used one of them.
+pub fn migrate(root: &str, symlink: &str, wait: bool) -> Result<()> {
+    if legacy_versions(root).is_empty() {
+        return Ok(());
+    }
+    let _lock = lock::acquire(root, lock::Mode::Exclusive, wait)?;
+    // another process may have migrated them while this one waited
+    for version_dir in legacy_versions(root) {
+        let a = migrate_version(&version_dir).map_err(|err| {
+            NvmError::io(
+                &format!("Move {} into its architecture dir", version_dir),
+                err,
+            )
+        })?;
+        let arch_dir = filepath::join(&version_dir, vec![a.name()]);
+        println!("Moved {} to {}", version_dir, arch_dir);
+        if fs::read_link(symlink).is_ok_and(|target| target == Path::new(&version_dir)) {
+            link::switch(symlink, &arch_dir)?;
+        }
+    }
+    Ok(())
+}
+
+// version dirs that still have files outside of an architecture dir
+fn legacy_versions(root: &str) -> Vec<String> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut versions = vec![];
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_version = name
+            .strip_prefix('v')
+            .is_some_and(|v| semver::Version::parse(v).is_ok());
+        // a symlinked version is someone else's tree, leave it alone
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_version && is_dir && !loose_entries(&entry.path()).is_empty() {
+            versions.push(entry.path().to_string_lossy().to_string());
+        }
+    }
+    versions.sort();
+    versions
+}
+
+fn loose_entries(version_dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(version_dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            !Arch::ALL.iter().any(|a| a.name() == name)
+        })
+        .map(|e| e.path())
+        .collect()
+}
+
+// move the loose entries into the dir of the architecture the node binary
+// was built for, picking up where an interrupted migration stopped
+fn migrate_version(version_dir: &str) -> std::io::Result<Arch> {
+    let dir = Path::new(version_dir);
+    // the nvm-windows style names the executable may have been renamed to
+    let exe = ["node", "node64", "node32"]
+        .iter()
+        .map(|name| dir.join("bin").join(name))
+        .find(|path| path.is_file());
+    let moved = Arch::ALL.into_iter().find(|a| dir.join(a.name()).is_dir());
+    let a = exe
+        .as_ref()
+        .and_then(|path| arch::of(&path.to_string_lossy()))
+        .or(moved)
+        .unwrap_or_else(Arch::host);
+
+    let arch_dir = dir.join(a.name());
+    fs::create_dir_all(&arch_dir)?;
+    for entry in loose_entries(dir) {
+        if let Some(name) = entry.file_name() {
+            fs::rename(&entry, arch_dir.join(name))?;
+        }
+    }
+    let node = arch_dir.join("bin").join("node");
+    if let Some(exe) = exe.filter(|_| !node.exists()) {
+        if let Some(name) = exe.file_name() {
+            fs::rename(arch_dir.join("bin").join(name), &node)?;
+        }
+    }
+    Ok(a)
+}
+
+#[cfg(test)]
+#[test]
+fn test_migrate() {
+    let root = std::env::temp_dir().join(format!("nvm-layout-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("v18.0.0").join("bin")).unwrap();
+    fs::create_dir_all(root.join("v20.0.0").join("lib")).unwrap();
+    fs::create_dir_all(root.join("v20.0.0").join("arm64").join("bin")).unwrap();
+    // the test binary stands in for a node build of the host
+    let exe = std::env::current_exe().unwrap();
+    fs::copy(&exe, root.join("v18.0.0").join("bin").join("node64")).unwrap();
+    let root_str = root.to_string_lossy().to_string();
+    let link = root.join("node").to_string_lossy().to_string();
+    let v18 = root.join("v18.0.0");
+    link::switch(&link, &v18.to_string_lossy()).unwrap();
+
+    assert_eq!(legacy_versions(&root_str).len(), 2);
+    // readers take an unmoved version as the host's
+    assert!(crate::common::node::is_version_installed(
+        &root_str,
+        "18.0.0",
+        Arch::host()
+    ));
+    migrate(&root_str, &link, false).unwrap();
+    assert!(legacy_versions(&root_str).is_empty());
+
+    let host = v18.join(Arch::host().name());
+    assert!(host.join("bin").join("node").is_file());
+    assert_eq!(fs::read_link(&link).unwrap(), host);
+    // without a node binary the architecture already there is used
+    assert!(root.join("v20.0.0").join("arm64").join("lib").is_dir());
+
+    fs::remove_dir_all(&root).unwrap();
+}
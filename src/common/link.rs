@@ -0,0 +1,65 @@
+use crate::common::error::{NvmError, Result};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Point `link` at `target` and return what it pointed at before. The new
+/// link is made under a temporary name and renamed over the old one, so
+/// there is no moment without a `node`.
+pub fn switch(link: &str, target: &str) -> Result<Option<PathBuf>> {
+    let previous = match fs::symlink_metadata(link) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            Some(fs::read_link(link).map_err(|err| NvmError::io(&format!("Read {}", link), err))?)
+        }
+        Ok(_) => {
+            return Err(NvmError::Other(format!(
+                "{} exists and is not a symlink, remove it first.",
+                link
+            )));
+        }
+        Err(_) => None,
+    };
+    replace(link, Path::new(target))?;
+    Ok(previous)
+}
+
+fn replace(link: &str, target: &Path) -> Result<()> {
+    let temp = format!("{}.{}.tmp", link, process::id());
+    let _ = fs::remove_file(&temp);
+    symlink(target, &temp).map_err(|err| {
+        NvmError::io(
+            &format!("Link {} to {}", temp, target.to_string_lossy()),
+            err,
+        )
+    })?;
+    if let Err(err) = fs::rename(&temp, link) {
+        let _ = fs::remove_file(&temp);
+        return Err(NvmError::io(&format!("Replace {}", link), err));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn test_switch() {
+    let dir = std::env::temp_dir().join(format!("nvm-link-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("v18.0.0")).unwrap();
+    fs::create_dir_all(dir.join("v20.0.0")).unwrap();
+    let link = dir.join("node").to_string_lossy().to_string();
+    let v18 = dir.join("v18.0.0").to_string_lossy().to_string();
+    let v20 = dir.join("v20.0.0").to_string_lossy().to_string();
+
+    assert_eq!(switch(&link, &v18).unwrap(), None);
+    let previous = switch(&link, &v20).unwrap();
+    assert_eq!(previous.as_deref(), Some(Path::new(&v18)));
+    assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from(&v20));
+
+    // a file in the way is not replaced
+    fs::remove_file(&link).unwrap();
+    fs::write(&link, "").unwrap();
+    assert!(switch(&link, &v18).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,111 @@
+use crate::common::error::{NvmError, Result};
+use crate::common::filepath;
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process;
+
+// advisory lock file under the root, holds the pid of the last process to take it
+pub const LOCK_FILE: &str = ".lock";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Commands that only read the root, such as list
+    Shared,
+    /// Commands that change versions, the symlink or settings
+    Exclusive,
+}
+
+/// Held for as long as the command runs, released when dropped.
+#[derive(Debug)]
+pub struct Lock {
+    file: File,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Lock the root for the command. Waits for other nvm processes unless
+/// `wait` is false, in which case a held lock is a `Busy` error. Nothing is
+/// locked when the root does not exist yet.
+pub fn acquire(root: &str, mode: Mode, wait: bool) -> Result<Option<Lock>> {
+    if root.is_empty() || !Path::new(root).is_dir() {
+        return Ok(None);
+    }
+    let path = filepath::join(root, vec![LOCK_FILE]);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|err| NvmError::io(&format!("Open {}", path), err))?;
+
+    if try_lock(&file, mode).is_err() {
+        let holder = match holder(&mut file) {
+            Some(pid) => format!("another nvm process (pid {})", pid),
+            None => "another nvm process".to_string(),
+        };
+        if !wait {
+            return Err(NvmError::Busy(format!("{} is locked by {}", root, holder)));
+        }
+        println!("Waiting for {}...", holder);
+        lock(&file, mode).map_err(|err| NvmError::io(&format!("Lock {}", path), err))?;
+    }
+
+    // readers share the lock, so the pid is only a hint of who holds it
+    let _ = file.set_len(0);
+    let _ = file.seek(SeekFrom::Start(0));
+    let _ = write!(file, "{}", process::id());
+    Ok(Some(Lock { file }))
+}
+
+fn try_lock(file: &File, mode: Mode) -> std::io::Result<()> {
+    match mode {
+        Mode::Shared => FileExt::try_lock_shared(file),
+        Mode::Exclusive => FileExt::try_lock_exclusive(file),
+    }
+}
+
+fn lock(file: &File, mode: Mode) -> std::io::Result<()> {
+    match mode {
+        Mode::Shared => FileExt::lock_shared(file),
+        Mode::Exclusive => FileExt::lock_exclusive(file),
+    }
+}
+
+// the pid written by the process holding the lock, windows does not let a
+// locked file be read so it is not always known
+fn holder(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+#[cfg(test)]
+#[test]
+fn test_acquire() {
+    let root = std::env::temp_dir().join(format!("nvm-lock-{}", process::id()));
+    let root = root.to_string_lossy().to_string();
+    let _ = std::fs::remove_dir_all(&root);
+
+    // a missing root is not locked
+    assert!(acquire(&root, Mode::Exclusive, false).unwrap().is_none());
+
+    std::fs::create_dir_all(&root).unwrap();
+    let shared = acquire(&root, Mode::Shared, false).unwrap();
+    assert!(acquire(&root, Mode::Shared, false).unwrap().is_some());
+    let err = acquire(&root, Mode::Exclusive, false).unwrap_err();
+    assert_eq!(err.exit_code(), 9);
+    assert!(err.to_string().contains(&format!("pid {}", process::id())));
+
+    drop(shared);
+    assert!(acquire(&root, Mode::Exclusive, false).unwrap().is_some());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -1,7 +1,19 @@
 pub mod arch;
+pub mod checksum;
 pub mod cmd;
+pub mod config;
+pub mod error;
 pub mod file;
 pub mod filepath;
+#[cfg(not(target_os = "windows"))]
+pub mod layout;
+#[cfg(not(target_os = "windows"))]
+pub mod link;
+pub mod lock;
 pub mod node;
+pub mod npm;
+pub mod schedule;
+pub mod shell;
+pub mod staging;
 pub mod strings;
 pub mod web;
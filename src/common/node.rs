@@ -1,8 +1,11 @@
+use crate::common::arch::{self, Arch};
+use crate::common::error::{NvmError, Result};
+use crate::common::schedule;
 use crate::common::web::WebContext;
-use crate::common::{arch, file};
+use crate::common::{file, filepath};
 use chrono::NaiveDate;
 use regex::Regex;
-use semver::Version;
+use semver::{Version, VersionReq};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
@@ -60,19 +63,18 @@ impl NodeInfo {
         return self.version.minor % 2 != 0;
     }
 
-    fn parse_node_info(json_value: Value) -> NodeInfo {
+    fn parse_node_info(json_value: Value) -> Option<NodeInfo> {
         let version: String = json_value["version"]
             .to_string()
             .replace("v", "")
             .replace("\"", "");
-        let version = Version::parse(version.as_str()).unwrap();
+        let version = Version::parse(version.as_str()).ok()?;
 
-        let date = json_value["date"].as_str().unwrap();
-        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let date = json_value["date"].as_str()?;
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
 
         let files = json_value["files"]
-            .as_array()
-            .unwrap()
+            .as_array()?
             .iter()
             .filter_map(|v| v.as_str().map(|s| s.to_string()))
             .collect();
@@ -91,13 +93,13 @@ impl NodeInfo {
 
         let lts = json_value["lts"].as_bool().unwrap_or(true);
 
-        let security = json_value["security"].as_bool().unwrap();
+        let security = json_value["security"].as_bool().unwrap_or(false);
 
         if version.to_string() == "0.11.16" {
             println!();
         }
 
-        NodeInfo {
+        Some(NodeInfo {
             version,
             date,
             files,
@@ -109,7 +111,7 @@ impl NodeInfo {
             modules,
             lts,
             security,
-        }
+        })
     }
 }
 
@@ -142,13 +144,15 @@ pub fn get_current_version() -> (String, String) {
         .trim_end_matches("\r\n")
         .to_string();
 
-    let out = Command::new("node")
+    let out = match Command::new("node")
         .arg("-p")
         .arg("console.log(process.execPath)")
         .output()
-        .ok()
-        .unwrap();
-    let mut str_p = String::from_utf8(out.stdout).ok().unwrap();
+    {
+        Ok(out) => out,
+        Err(_) => return ("Unknown".to_string(), "".to_string()),
+    };
+    let mut str_p = String::from_utf8_lossy(&out.stdout).to_string();
     str_p = regex::Regex::new("undefined")
         .unwrap()
         .replace_all(str_p.as_str(), "")
@@ -158,136 +162,122 @@ pub fn get_current_version() -> (String, String) {
         .trim_end_matches("\n")
         .to_string();
 
-    let mut bit = arch::bit(&file);
-    if bit == "?" {
-        let out = Command::new("node")
-            .arg("-v")
-            .arg("console.log(process.arch)")
-            .output()
-            .ok()
-            .unwrap();
-        let res = String::from_utf8(out.stdout);
-        if res.is_err() {
-            println!("{}", res.err().unwrap());
-            return ("Unknown".to_string(), "".to_string());
+    if let Some(a) = arch::of(&file) {
+        return (v, a.to_string());
+    }
+    let out = match Command::new("node").arg("-p").arg("process.arch").output() {
+        Ok(out) => out,
+        Err(_) => return ("Unknown".to_string(), "".to_string()),
+    };
+    let name = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    let a = Arch::parse(&name)
+        .map(|a| a.to_string())
+        .unwrap_or_default();
+    (v, a)
+}
+
+/// Where the build of an architecture sits inside a version dir, its own
+/// `{arch}` dir on linux and mac. Windows keeps the nvm-windows layout, with
+/// node32.exe and node64.exe side by side in the version dir.
+#[cfg(target_os = "windows")]
+pub fn arch_dir(version_dir: &str, _a: Arch) -> String {
+    version_dir.to_string()
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn arch_dir(version_dir: &str, a: Arch) -> String {
+    filepath::join(version_dir, vec![a.name()])
+}
+
+// the node executable of a version, suffix is "", "32" or "64"
+#[cfg(target_os = "windows")]
+fn node_exe(root: &str, version: &str, suffix: &str) -> String {
+    format!("{}\\v{}\\node{}.exe", root, version, suffix)
+}
+
+#[cfg(target_os = "windows")]
+pub fn is_version_installed(root: &str, version: &str, a: Arch) -> bool {
+    let node_path = node_exe(root, version, "");
+    let used = file::exists(&node_path);
+    // 32 and 64 bit x86 builds can sit side by side as node32 and node64,
+    // with the one in use renamed to node
+    if a == Arch::X86 || a == Arch::X64 {
+        let e32 = file::exists(&node_exe(root, version, "32"));
+        let e64 = file::exists(&node_exe(root, version, "64"));
+        if file::exists(&node_exe(root, version, a.bits())) {
+            return true;
         }
-        let str = res.ok().unwrap();
-        if str.as_str() == "x64" {
-            bit = "64"
-        } else {
-            bit = "32"
+        if ((e32 || e64) && used) || (e32 && e64) {
+            return true;
         }
     }
-    (v, bit.to_string())
+    used && arch::of(&node_path) == Some(a)
 }
 
-#[cfg(target_os = "windows")]
-pub fn is_version_installed(root: &str, version: &str, cpu: &str) -> bool {
-    let e32 = file::exists(&format!("{}\\v{}\\node32.exe", root, version));
-    let e64 = file::exists(&format!("{}\\v{}\\node64.exe", root, version));
-    let used = file::exists(&format!("{}\\v{}\\node.exe", root, version));
-    if cpu == "all" {
-        return ((e32 || e64) && used) || e32 && e64;
-    }
-    let mut node_path = format!("{}\\v{}\\node{}.exe", root, version, cpu);
-    if file::exists(&node_path) {
-        return true;
-    }
-    if ((e32 || e64) && used) || (e32 && e64) {
-        return true;
-    }
-    node_path = format!("{}\\v{}\\node.exe", root, version);
-    if !e32 && !e64 && used && arch::validate(cpu) == arch::bit(&node_path) {
-        return true;
-    }
-    if cpu == "32" {
-        return e32;
-    }
-    if cpu == "64" {
-        return e64;
-    }
-    false
+// a version still extracted straight into v{version} counts as the host's
+// until a command that locks the root exclusively moves it to its arch dir
+#[cfg(not(target_os = "windows"))]
+pub fn is_version_installed(root: &str, version: &str, a: Arch) -> bool {
+    let version_dir = filepath::join(root, vec![&format!("v{}", version)]);
+    let legacy = || {
+        ["node", "node64", "node32"]
+            .iter()
+            .any(|name| file::exists(&filepath::join(&version_dir, vec!["bin", name])))
+    };
+    file::exists(&filepath::join(
+        &arch_dir(&version_dir, a),
+        vec!["bin", "node"],
+    )) || a == Arch::host() && legacy()
 }
 
-#[cfg(target_os = "linux")]
-pub fn is_version_installed(root: &str, version: &str, cpu: &str) -> bool {
-    let e32 = file::exists(&format!("{}/v{}/bin/node32", root, version));
-    let e64 = file::exists(&format!("{}/v{}/bin/node64", root, version));
-    let used = file::exists(&format!("{}/v{}/bin/node", root, version));
-    if cpu == "all" {
-        return ((e32 || e64) && used) || e32 && e64;
-    }
-    let mut node_path = format!("{}/v{}/bin/node{}", root, version, cpu);
-    if file::exists(&node_path) {
-        return true;
-    }
-    if ((e32 || e64) && used) || (e32 && e64) {
-        return true;
-    }
-    node_path = format!("{}/v{}/bin/node", root, version);
-    if !e32 && !e64 && used && arch::validate(cpu) == arch::bit(&node_path) {
-        return true;
-    }
-    if cpu == "32" {
-        return e32;
-    }
-    if cpu == "64" {
-        return e64;
-    }
-    false
+/// The architectures a version is installed for.
+pub fn installed_arches(root: &str, version: &str) -> Vec<Arch> {
+    Arch::ALL
+        .into_iter()
+        .filter(|a| is_version_installed(root, version, *a))
+        .collect()
 }
 
-#[cfg(target_os = "macos")]
-pub fn is_version_installed(root: &str, version: &str, cpu: &str) -> bool {
-    let e32 = file::exists(&format!("{}/v{}/bin/node32", root, version));
-    let e64 = file::exists(&format!("{}/v{}/bin/node64", root, version));
-    let used = file::exists(&format!("{}/v{}/bin/node", root, version));
-    if cpu == "all" {
-        return ((e32 || e64) && used) || e32 && e64;
-    }
-    let mut node_path = format!("{}/v{}/bin/node{}", root, version, cpu);
-    if file::exists(&node_path) {
-        return true;
-    }
-    if ((e32 || e64) && used) || (e32 && e64) {
-        return true;
-    }
-    node_path = format!("{}/v{}/bin/node", root, version);
-    if !e32 && !e64 && used && arch::validate(cpu) == arch::bit(&node_path) {
-        return true;
-    }
-    if cpu == "32" {
-        return e32;
-    }
-    if cpu == "64" {
-        return e64;
-    }
-    false
+pub fn is_version_available(v: &str, web_ctx: &WebContext) -> Result<bool> {
+    let tmp = Version::parse(v).map_err(|err| {
+        NvmError::Resolution(format!("\"{}\" is not a valid version, {}", v, err))
+    })?;
+    let (avail, _, _, _, _, _) = get_available(web_ctx)?;
+    Ok(avail.contains(&tmp))
 }
 
-pub fn is_version_available(v: &str, web_ctx: &WebContext) -> bool {
-    let tmp = Version::parse(v).unwrap();
-    let (avail, _, _, _, _, _) = get_available(web_ctx);
-    for b in avail {
-        if b.eq(&tmp) {
-            return true;
+/// The installed versions a spec picks: 16.20.2 is that version, 14 and
+/// 16.20 every installed patch of the line, anything else a semver range such
+/// as ">=12 <15".
+pub fn version_req(spec: &str) -> Option<VersionReq> {
+    let spec = spec.trim().trim_start_matches('v');
+    if Version::parse(spec).is_ok() {
+        return VersionReq::parse(&format!("={}", spec)).ok();
+    }
+    if !spec.is_empty() && spec.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return VersionReq::parse(&format!("~{}", spec)).ok();
+    }
+    // npm separates comparators with spaces, semver with commas
+    let mut comparators: Vec<String> = vec![];
+    for token in spec.split_whitespace() {
+        match comparators.last_mut() {
+            Some(op) if op.chars().all(|c| "<>=~^".contains(c)) => op.push_str(token),
+            _ => comparators.push(token.to_string()),
         }
     }
-    false
+    VersionReq::parse(&comparators.join(", ")).ok()
 }
 
-pub fn get_installed(root: &str) -> Vec<String> {
+pub fn get_installed(root: &str) -> Result<Vec<String>> {
     let mut list: Vec<Version> = Vec::new();
-    let result = fs::read_dir(root).unwrap();
+    let result = fs::read_dir(root).map_err(|err| NvmError::io(&format!("read {}", root), err))?;
     for f in result {
-        let d = f.unwrap();
-        let mut file_name = d.file_name().into_string().unwrap();
-        let info = d.metadata().unwrap();
+        let d = f?;
+        let file_name = d.file_name().to_string_lossy().to_string();
+        let info = d.metadata()?;
         if info.is_dir() || (info.is_symlink()) {
-            let re = regex::Regex::new("v").unwrap();
-            if re.is_match(file_name.as_str()) {
-                file_name = file_name.replace("v", "");
-                let current_version = semver::Version::parse(file_name.as_str()).unwrap();
+            // anything that is not v<semver> is not an installed version
+            if let Some(Ok(current_version)) = file_name.strip_prefix('v').map(Version::parse) {
                 list.push(current_version);
             }
         }
@@ -296,22 +286,38 @@ pub fn get_installed(root: &str) -> Vec<String> {
 
     let mut log_gable_list: Vec<String> = Vec::new();
     for v in list {
-        log_gable_list.push(format!("v{}", v.to_string()));
+        log_gable_list.push(format!("v{}", v));
     }
     log_gable_list.reverse();
-    log_gable_list
+    Ok(log_gable_list)
+}
+
+// every release in the mirror's index.json, newest first
+fn get_index(web_context: &WebContext) -> Result<Vec<NodeInfo>> {
+    let url = web_context.get_full_node_url("index.json");
+    let text = web_context.get_remote_text_file(url.as_str())?;
+
+    // 反序列化 JSON 字符串为 NodeInfo 结构体的 Vec
+    let list: Vec<Value> = serde_json::from_str(&text)
+        .map_err(|err| NvmError::Network(format!("{} is not a release index, {}", url, err)))?;
+    list.into_iter()
+        .map(|v| {
+            NodeInfo::parse_node_info(v)
+                .ok_or_else(|| NvmError::Network(format!("{} has an invalid release entry", url)))
+        })
+        .collect()
 }
 
 pub fn get_available(
     web_context: &WebContext,
-) -> (
+) -> Result<(
     Vec<Version>,
     Vec<Version>,
     Vec<Version>,
     Vec<Version>,
     Vec<Version>,
     HashMap<String, String>,
-) {
+)> {
     let mut all: Vec<Version> = Vec::new();
     let mut lts: Vec<Version> = Vec::new();
     let mut current: Vec<Version> = Vec::new();
@@ -319,14 +325,7 @@ pub fn get_available(
     let mut unstable: Vec<Version> = Vec::new();
 
     let mut npm: HashMap<String, String> = HashMap::new();
-    let url = web_context.get_full_node_url("index.json");
-    let text = web_context.get_remote_text_file(url.as_str());
-
-    // 反序列化 JSON 字符串为 NodeInfo 结构体的 Vec
-    let list: Vec<Value> = serde_json::from_str(&text).unwrap();
-    for v in list {
-        let node = NodeInfo::parse_node_info(v);
-
+    for node in get_index(web_context)? {
         all.push(node.version.clone());
         npm.insert(node.version.to_string(), node.npm.clone());
 
@@ -340,34 +339,63 @@ pub fn get_available(
             unstable.push(node.version.clone())
         }
     }
-    (all, lts, current, stable, unstable, npm)
+    Ok((all, lts, current, stable, unstable, npm))
 }
 
-pub fn get_npm_version(node_version: &str, web_context: &WebContext) -> String {
-    let (_, _, _, _, _, npm) = get_available(web_context);
-    npm.get(&node_version.to_string()).unwrap().to_string()
+/// The releases that were published as security releases, newest first.
+pub fn get_security_releases(web_context: &WebContext) -> Result<Vec<Version>> {
+    Ok(get_index(web_context)?
+        .into_iter()
+        .filter(|node| node.security)
+        .map(|node| node.version)
+        .collect())
 }
 
-pub fn get_latest(web_context: &WebContext) -> Option<String> {
+/// The security releases of the version's line that came out after it,
+/// newest first. Installing the first one picks up all of their fixes.
+pub fn missed_security_releases(version: &Version, security: &[Version]) -> Vec<Version> {
+    let line = schedule::line_name(version);
+    security
+        .iter()
+        .filter(|v| *v > version && schedule::line_name(v) == line)
+        .cloned()
+        .collect()
+}
+
+pub fn get_npm_version(node_version: &str, web_context: &WebContext) -> Result<String> {
+    let (_, _, _, _, _, npm) = get_available(web_context)?;
+    npm.get(node_version).cloned().ok_or_else(|| {
+        NvmError::Resolution(format!(
+            "no npm release is known for node v{}",
+            node_version
+        ))
+    })
+}
+
+pub fn get_latest(web_context: &WebContext) -> Result<String> {
     let url = web_context.get_full_node_url("latest/SHASUMS256.txt");
-    let content = web_context.get_remote_text_file(url.as_str());
+    let content = web_context.get_remote_text_file(url.as_str())?;
 
     let re = Regex::new("node-v(.+)+msi").unwrap();
     let reg = Regex::new("node-v|-[xa].+").unwrap();
 
-    if let Some(res) = re.find(content.as_str()) {
-        Some(reg.replace_all(res.as_str(), "").to_string())
-    } else {
-        None
+    match re.find(content.as_str()) {
+        Some(res) => Ok(reg.replace_all(res.as_str(), "").to_string()),
+        None => Err(NvmError::Resolution(format!(
+            "could not find the latest version in {}",
+            url
+        ))),
     }
 }
 
-pub fn get_lts(web_context: &WebContext) -> Option<String> {
-    let (_, lts_list, _, _, _, _) = get_available(web_context);
-    if let Some(v) = lts_list.get(0) {
-        return Some(v.to_string());
+pub fn get_lts(web_context: &WebContext) -> Result<String> {
+    let (_, lts_list, _, _, _, _) = get_available(web_context)?;
+    match lts_list.first() {
+        Some(v) => Ok(v.to_string()),
+        None => Err(NvmError::Resolution(
+            "no LTS release found in the release index".to_string(),
+        )),
     }
-    None
 }
 
 #[cfg(test)]
@@ -387,5 +415,38 @@ fn test_get_available() {
 #[test]
 fn test_is_version_available() {
     let web_ctx: WebContext = WebContext::new();
-    assert_eq!(is_version_available("14.16.0", &web_ctx), true);
+    assert_eq!(is_version_available("14.16.0", &web_ctx).unwrap(), true);
+}
+
+#[cfg(test)]
+#[test]
+fn test_version_req() {
+    let matches = |spec: &str, v: &str| {
+        version_req(spec)
+            .unwrap()
+            .matches(&Version::parse(v).unwrap())
+    };
+    assert!(matches("16.20.2", "16.20.2"));
+    assert!(!matches("v16.20.2", "16.20.3"));
+    assert!(matches("14", "14.21.3"));
+    assert!(!matches("14", "15.0.0"));
+    assert!(matches("16.20", "16.20.1"));
+    assert!(!matches("16.20", "16.21.0"));
+    assert!(matches(">=12 <15", "13.14.0"));
+    assert!(matches(">= 12 < 15", "12.0.0"));
+    assert!(!matches(">=12 <15", "15.0.0"));
+    assert!(version_req("lts").is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn test_missed_security_releases() {
+    let v = |s: &str| Version::parse(s).unwrap();
+    let security = vec![v("20.11.1"), v("18.19.1"), v("18.18.2"), v("16.20.2")];
+    assert_eq!(
+        missed_security_releases(&v("18.18.0"), &security),
+        vec![v("18.19.1"), v("18.18.2")]
+    );
+    assert!(missed_security_releases(&v("18.19.1"), &security).is_empty());
+    assert!(missed_security_releases(&v("19.0.0"), &security).is_empty());
 }
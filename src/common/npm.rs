@@ -0,0 +1,143 @@
+use crate::common::filepath;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+// global packages that ship with node itself
+const BUNDLED: [&str; 2] = ["npm", "corepack"];
+// file under the root listing the packages every new install gets
+pub const DEFAULT_PACKAGES_FILE: &str = "default-packages";
+
+/// The dir of an install holding node, npm and the binaries of global packages.
+#[cfg(target_os = "windows")]
+pub fn bin_dir(install_dir: &str) -> String {
+    install_dir.to_string()
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn bin_dir(install_dir: &str) -> String {
+    filepath::join(install_dir, vec!["bin"])
+}
+
+#[cfg(target_os = "windows")]
+fn modules_dir(install_dir: &str) -> String {
+    filepath::join(install_dir, vec!["node_modules"])
+}
+
+#[cfg(not(target_os = "windows"))]
+fn modules_dir(install_dir: &str) -> String {
+    filepath::join(install_dir, vec!["lib", "node_modules"])
+}
+
+#[cfg(target_os = "windows")]
+fn program_path(bin_dir: &str, program: &str) -> String {
+    filepath::join(bin_dir, vec![&format!("{}.cmd", program)])
+}
+
+#[cfg(not(target_os = "windows"))]
+fn program_path(bin_dir: &str, program: &str) -> String {
+    filepath::join(bin_dir, vec![program])
+}
+
+/// The global packages installed into an install, scoped ones as
+/// `@scope/name`, leaving out the ones bundled with node.
+pub fn globals(install_dir: &str) -> Vec<String> {
+    let entries = match fs::read_dir(modules_dir(install_dir)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut names = vec![];
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || BUNDLED.contains(&name.as_str()) {
+            continue;
+        }
+        if !name.starts_with('@') {
+            names.push(name);
+            continue;
+        }
+        if let Ok(scoped) = fs::read_dir(entry.path()) {
+            for package in scoped.flatten() {
+                names.push(format!(
+                    "{}/{}",
+                    name,
+                    package.file_name().to_string_lossy()
+                ));
+            }
+        }
+    }
+    names.sort();
+    names
+}
+
+/// The package specs in `{root}/default-packages`, empty when there is no
+/// such file.
+pub fn default_packages(root: &str) -> Vec<String> {
+    fs::read_to_string(filepath::join(root, vec![DEFAULT_PACKAGES_FILE]))
+        .map(|text| parse_package_list(&text))
+        .unwrap_or_default()
+}
+
+// one spec per line, # starts a comment unless it is part of a spec such
+// as github:user/repo#branch
+fn parse_package_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| {
+            let end = line
+                .char_indices()
+                .find(|&(i, c)| c == '#' && (i == 0 || line[..i].ends_with(char::is_whitespace)))
+                .map_or(line.len(), |(i, _)| i);
+            line[..end].trim().to_string()
+        })
+        .filter(|spec| !spec.is_empty())
+        .collect()
+}
+
+/// Whether the install ships a command, corepack came with 14.19 and 16.9.
+pub fn has(install_dir: &str, program: &str) -> bool {
+    Path::new(&program_path(&bin_dir(install_dir), program)).is_file()
+}
+
+/// Run a command of the install, such as npm or corepack, with its bin dir
+/// first on PATH so that it runs on the node next to it.
+pub fn run(install_dir: &str, program: &str, args: &[&str]) -> io::Result<ExitStatus> {
+    let bin = bin_dir(install_dir);
+    let mut paths = vec![PathBuf::from(&bin)];
+    if let Some(path) = env::var_os("PATH") {
+        paths.extend(env::split_paths(&path));
+    }
+    let path =
+        env::join_paths(paths).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    Command::new(program_path(&bin, program))
+        .args(args)
+        .env("PATH", path)
+        .status()
+}
+
+#[cfg(test)]
+#[test]
+fn test_globals() {
+    let dir = env::temp_dir().join(format!("nvm-npm-{}", std::process::id()));
+    let install_dir = dir.to_string_lossy().to_string();
+    let modules = PathBuf::from(modules_dir(&install_dir));
+    for package in ["npm", "corepack", "typescript", "@vue/cli", ".bin"] {
+        fs::create_dir_all(modules.join(package)).unwrap();
+    }
+    assert_eq!(globals(&install_dir), vec!["@vue/cli", "typescript"]);
+    assert!(globals(&filepath::join(&install_dir, vec!["missing"])).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_package_list() {
+    let text =
+        "# tools for every version\ntypescript\n\n  pnpm@9  # pinned\ngithub:user/repo#main\n";
+    assert_eq!(
+        parse_package_list(text),
+        vec!["typescript", "pnpm@9", "github:user/repo#main"]
+    );
+    assert!(parse_package_list("\n# nothing yet\n").is_empty());
+}
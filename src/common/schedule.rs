@@ -0,0 +1,176 @@
+use crate::common::error::{NvmError, Result};
+use crate::common::filepath;
+use crate::common::web::WebContext;
+use chrono::NaiveDate;
+use semver::Version;
+use serde_json::Value;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+// the release working group's dates for every release line
+pub const SCHEDULE_URL: &str =
+    "https://raw.githubusercontent.com/nodejs/Release/main/schedule.json";
+// directory under the root for files fetched from outside the mirror
+pub const CACHE_DIR: &str = "cache";
+const CACHE_FILE: &str = "schedule.json";
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Where a release line is in its life.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Pending,
+    Current,
+    ActiveLts,
+    Maintenance,
+    EndOfLife,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Pending => "Pending",
+            Phase::Current => "Current",
+            Phase::ActiveLts => "Active LTS",
+            Phase::Maintenance => "Maintenance",
+            Phase::EndOfLife => "End-of-life",
+        }
+    }
+}
+
+/// A release line such as v18 or v0.12 and the dates it moves through.
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub name: String,
+    pub start: NaiveDate,
+    pub lts: Option<NaiveDate>,
+    pub maintenance: Option<NaiveDate>,
+    pub end: NaiveDate,
+    pub codename: String,
+}
+
+impl Line {
+    /// Past its end of life and no longer getting security fixes.
+    pub fn is_eol(&self, today: NaiveDate) -> bool {
+        today > self.end
+    }
+
+    pub fn phase(&self, today: NaiveDate) -> Phase {
+        if today < self.start {
+            Phase::Pending
+        } else if self.is_eol(today) {
+            Phase::EndOfLife
+        } else if self.maintenance.is_some_and(|d| today >= d) {
+            Phase::Maintenance
+        } else if self.lts.is_some_and(|d| today >= d) {
+            Phase::ActiveLts
+        } else {
+            Phase::Current
+        }
+    }
+}
+
+/// The schedule, from `{root}/cache` when it was fetched less than a day ago.
+/// An older copy is used when it can not be downloaded.
+pub fn load(web_ctx: &WebContext, root: &str) -> Result<Vec<Line>> {
+    let path = filepath::join(root, vec![CACHE_DIR, CACHE_FILE]);
+    let cached = cached(root);
+    let fresh = fs::metadata(&path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < MAX_AGE);
+    if let Some(lines) = cached.as_ref().filter(|_| fresh) {
+        return Ok(lines.clone());
+    }
+
+    let downloaded = web_ctx.get_remote_text_file(SCHEDULE_URL).and_then(|text| {
+        let lines = parse(&text).ok_or_else(|| {
+            NvmError::Network(format!("{} is not a release schedule", SCHEDULE_URL))
+        })?;
+        store(root, &path, &text);
+        Ok(lines)
+    });
+    match (downloaded, cached) {
+        (Ok(lines), _) => Ok(lines),
+        (Err(_), Some(lines)) => Ok(lines),
+        (Err(err), None) => Err(err),
+    }
+}
+
+/// The schedule last fetched into `{root}/cache`, however old, for commands
+/// that should not go to the network.
+pub fn cached(root: &str) -> Option<Vec<Line>> {
+    let path = filepath::join(root, vec![CACHE_DIR, CACHE_FILE]);
+    fs::read_to_string(path).ok().and_then(|text| parse(&text))
+}
+
+// written aside and renamed so readers never see half a file
+fn store(root: &str, path: &str, text: &str) {
+    let dir = filepath::join(root, vec![CACHE_DIR]);
+    let temp = format!("{}.{}.tmp", path, process::id());
+    if fs::create_dir_all(dir).is_ok() && fs::write(&temp, text).is_ok() {
+        let _ = fs::rename(&temp, path);
+    }
+    let _ = fs::remove_file(&temp);
+}
+
+/// The lines of a schedule.json, oldest first.
+pub fn parse(text: &str) -> Option<Vec<Line>> {
+    let value: Value = serde_json::from_str(text).ok()?;
+    let date = |v: &Value, key: &str| {
+        v[key]
+            .as_str()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    };
+    let mut lines = vec![];
+    for (name, v) in value.as_object()? {
+        lines.push(Line {
+            name: name.to_string(),
+            start: date(v, "start")?,
+            lts: date(v, "lts"),
+            maintenance: date(v, "maintenance"),
+            end: date(v, "end")?,
+            codename: v["codename"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+    lines.sort_by_key(|l| l.start);
+    Some(lines)
+}
+
+/// The name of the line a version belongs to, v0.12 before 1.0 and v18 after.
+pub fn line_name(v: &Version) -> String {
+    if v.major == 0 {
+        format!("v0.{}", v.minor)
+    } else {
+        format!("v{}", v.major)
+    }
+}
+
+pub fn find<'a>(lines: &'a [Line], v: &Version) -> Option<&'a Line> {
+    let name = line_name(v);
+    lines.iter().find(|l| l.name == name)
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse() {
+    let text = r#"{
+        "v0.12": {"start": "2015-02-06", "end": "2016-12-31"},
+        "v18": {"start": "2022-04-19", "lts": "2022-10-25", "maintenance": "2023-10-18",
+                "end": "2025-04-30", "codename": "Hydrogen"}
+    }"#;
+    let lines = parse(text).unwrap();
+    assert_eq!(lines[0].name, "v0.12");
+    let v18 = find(&lines, &Version::new(18, 20, 1)).unwrap();
+    assert_eq!(v18.codename, "Hydrogen");
+    let day = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+    assert!(!v18.is_eol(day("2025-04-30")));
+    assert!(v18.is_eol(day("2025-05-01")));
+    assert_eq!(v18.phase(day("2022-05-01")), Phase::Current);
+    assert_eq!(v18.phase(day("2023-01-01")), Phase::ActiveLts);
+    assert_eq!(v18.phase(day("2024-01-01")), Phase::Maintenance);
+    assert_eq!(v18.phase(day("2025-05-01")), Phase::EndOfLife);
+    assert!(find(&lines, &Version::new(0, 12, 18)).is_some());
+    assert!(find(&lines, &Version::new(20, 0, 0)).is_none());
+}
@@ -0,0 +1,175 @@
+use std::env;
+use std::path::PathBuf;
+
+// marks the lines nvm owns in a shell rc file
+const BLOCK_START: &str = "# >>> nvm-rs >>>";
+const BLOCK_END: &str = "# <<< nvm-rs <<<";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn parse(name: &str) -> Option<Shell> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+
+    /// The shell from `$SHELL`, bash when it is unknown.
+    pub fn detect() -> Shell {
+        let shell = env::var("SHELL").unwrap_or_default();
+        let name = shell.rsplit('/').next().unwrap_or("");
+        Shell::parse(name).unwrap_or(Shell::Bash)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    /// The startup file the block is written to.
+    pub fn rc_file(&self) -> Option<PathBuf> {
+        let home = PathBuf::from(env::var("HOME").ok()?);
+        let path = match self {
+            #[cfg(target_os = "macos")]
+            Shell::Bash => home.join(".bash_profile"),
+            #[cfg(not(target_os = "macos"))]
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => match env::var("ZDOTDIR") {
+                Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join(".zshrc"),
+                _ => home.join(".zshrc"),
+            },
+            Shell::Fish => match env::var("XDG_CONFIG_HOME") {
+                Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("fish").join("config.fish"),
+                _ => home.join(".config").join("fish").join("config.fish"),
+            },
+        };
+        Some(path)
+    }
+
+    /// The marked block adding the nvm executable and the active node to PATH.
+    pub fn block(&self, paths: &[&str]) -> String {
+        let line = match self {
+            Shell::Fish => format!("set -gx PATH {} $PATH", quote_all(paths)),
+            _ => format!("export PATH=\"{}:$PATH\"", paths.join(":")),
+        };
+        format!("{}\n{}\n{}\n", BLOCK_START, line, BLOCK_END)
+    }
+}
+
+fn quote_all(paths: &[&str]) -> String {
+    paths
+        .iter()
+        .map(|p| format!("\"{}\"", p))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Replace the nvm block in the content, or append it when there is none.
+pub fn upsert_block(content: &str, block: &str) -> String {
+    match find_block(content) {
+        Some((start, end)) => format!("{}{}{}", &content[..start], block, &content[end..]),
+        None => {
+            let mut res = content.to_string();
+            if !res.is_empty() && !res.ends_with('\n') {
+                res.push('\n');
+            }
+            res.push_str(block);
+            res
+        }
+    }
+}
+
+/// Remove the nvm block from the content, if there is one.
+pub fn remove_block(content: &str) -> String {
+    match find_block(content) {
+        Some((start, end)) => format!("{}{}", &content[..start], &content[end..]),
+        None => content.to_string(),
+    }
+}
+
+/// Remove the nvm block and the lines older install scripts appended: the
+/// `NVM_HOME`/`NVM_SYMLINK` exports and the PATH entry for `{symlink}/bin`.
+pub fn strip_nvm_lines(content: &str, symlink: &str) -> String {
+    let bin_dir = format!("{}/bin", symlink.trim_end_matches('/'));
+    let content = remove_block(content);
+    let mut res = String::new();
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let is_export = trimmed.starts_with("export ") || trimmed.starts_with("set ");
+        let nvm_var = trimmed.contains("NVM_HOME") || trimmed.contains("NVM_SYMLINK");
+        let nvm_path =
+            trimmed.contains("PATH") && !symlink.is_empty() && trimmed.contains(&bin_dir);
+        if is_export && (nvm_var || nvm_path) {
+            continue;
+        }
+        res.push_str(line);
+    }
+    res
+}
+
+/// Every rc file nvm or its install scripts may have written to.
+pub fn rc_files() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = [Shell::Bash, Shell::Zsh, Shell::Fish]
+        .iter()
+        .filter_map(|s| s.rc_file())
+        .collect();
+    if let Ok(home) = env::var("HOME") {
+        for name in [".bashrc", ".bash_profile", ".profile", ".zprofile"] {
+            let path = PathBuf::from(&home).join(name);
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+// byte range of the block including the trailing newline
+fn find_block(content: &str) -> Option<(usize, usize)> {
+    let start = content.find(BLOCK_START)?;
+    let end = start + content[start..].find(BLOCK_END)? + BLOCK_END.len();
+    let end = if content[end..].starts_with('\n') {
+        end + 1
+    } else {
+        end
+    };
+    Some((start, end))
+}
+
+#[cfg(test)]
+#[test]
+fn test_upsert_block() {
+    let block = Shell::Bash.block(&["/opt/nvm", "/opt/nvm/node/bin"]);
+    let content = upsert_block("alias ll='ls -l'", &block);
+    assert_eq!(
+        content,
+        "alias ll='ls -l'\n# >>> nvm-rs >>>\nexport PATH=\"/opt/nvm:/opt/nvm/node/bin:$PATH\"\n# <<< nvm-rs <<<\n"
+    );
+    assert_eq!(upsert_block(&content, &block), content);
+
+    let updated = upsert_block(&content, &Shell::Bash.block(&["/usr/local/nvm"]));
+    assert_eq!(updated.matches(BLOCK_START).count(), 1);
+    assert!(updated.contains("/usr/local/nvm:$PATH"));
+    assert_eq!(remove_block(&updated), "alias ll='ls -l'\n");
+}
+
+#[cfg(test)]
+#[test]
+fn test_strip_nvm_lines() {
+    let content = "alias ll='ls -l'\nexport NVM_HOME=/home/a/.nvm\nexport NVM_SYMLINK=/home/a/.nvm/node\nexport PATH=/usr/bin:/home/a/.nvm:/home/a/.nvm/node/bin\nexport PATH=$HOME/bin:$PATH\n";
+    assert_eq!(
+        strip_nvm_lines(content, "/home/a/.nvm/node"),
+        "alias ll='ls -l'\nexport PATH=$HOME/bin:$PATH\n"
+    );
+}
@@ -0,0 +1,133 @@
+use crate::common::error::{NvmError, Result};
+use crate::common::filepath;
+use std::path::Path;
+use std::{fs, io, process};
+
+// directory under the root where versions are downloaded and extracted
+pub const STAGING_DIR: &str = ".staging";
+// written once a staged version is complete, holds the name of its final dir
+const MARKER: &str = ".complete";
+
+/// A fresh staging directory for the version, unique to this process.
+pub fn create(root: &str, version: &str) -> Result<String> {
+    let name = format!("v{}-{}", version, process::id());
+    let dir = filepath::join(root, vec![STAGING_DIR, &name]);
+    if Path::new(&dir).exists() {
+        fs::remove_dir_all(&dir).map_err(|err| NvmError::io(&format!("Remove {}", dir), err))?;
+    }
+    fs::create_dir_all(&dir).map_err(|err| NvmError::io(&format!("Create {}", dir), err))?;
+    Ok(dir)
+}
+
+/// Mark the staged version complete and move it to `{root}/{name}`.
+pub fn finish(staging: &str, root: &str, name: &str) -> Result<()> {
+    let marker = filepath::join(staging, vec![MARKER]);
+    fs::write(&marker, name).map_err(|err| NvmError::io(&format!("Write {}", marker), err))?;
+    commit(staging, root, name)
+        .map_err(|err| NvmError::io(&format!("Move {} into {}", staging, root), err))
+}
+
+/// Finish the versions that were completely staged when an earlier run
+/// stopped, and remove the ones that were not.
+pub fn clean(root: &str) {
+    let dir = filepath::join(root, vec![STAGING_DIR]);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let staging = entry.path().to_string_lossy().to_string();
+        let name = fs::read_to_string(filepath::join(&staging, vec![MARKER])).unwrap_or_default();
+        let name = name.trim();
+        let valid = name.starts_with('v') && !name.contains(['/', '\\']) && name != "v";
+        if valid && !installed(&staging, root, name) && commit(&staging, root, name).is_ok() {
+            println!("Finished the interrupted install of {}", name);
+            continue;
+        }
+        let _ = fs::remove_dir_all(entry.path());
+    }
+    let _ = fs::remove_dir(&dir);
+}
+
+// whether what was staged, such as the v{version}/{arch} dir of one
+// architecture, has been installed since; another architecture of the
+// version being there is no reason to throw it away
+fn installed(staging: &str, root: &str, name: &str) -> bool {
+    let target = Path::new(root).join(name);
+    let entries = match fs::read_dir(staging) {
+        Ok(entries) => entries,
+        Err(_) => return true,
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name() != MARKER)
+        .any(|e| target.join(e.file_name()).exists())
+}
+
+// rename the staging dir into place; when the target already exists, which
+// happens when another architecture of the version is installed, the staged
+// entries are moved into it instead
+fn commit(staging: &str, root: &str, name: &str) -> io::Result<()> {
+    let target = filepath::join(root, vec![name]);
+    if !Path::new(&target).exists() {
+        fs::rename(staging, &target)?;
+        let _ = fs::remove_file(filepath::join(&target, vec![MARKER]));
+        return Ok(());
+    }
+    for entry in fs::read_dir(staging)? {
+        let entry = entry?;
+        if entry.file_name() == MARKER {
+            continue;
+        }
+        let dest = Path::new(&target).join(entry.file_name());
+        if dest.is_dir() {
+            fs::remove_dir_all(&dest)?;
+        } else if dest.exists() {
+            fs::remove_file(&dest)?;
+        }
+        fs::rename(entry.path(), dest)?;
+    }
+    fs::remove_dir_all(staging)
+}
+
+#[cfg(test)]
+#[test]
+fn test_finish_and_clean() {
+    let root = std::env::temp_dir().join(format!("nvm-staging-{}", process::id()));
+    let root = root.to_string_lossy().to_string();
+    let _ = fs::remove_dir_all(&root);
+
+    // a finished install is renamed into place without its marker
+    let staging = create(&root, "20.1.0").unwrap();
+    fs::write(filepath::join(&staging, vec!["node"]), "").unwrap();
+    finish(&staging, &root, "v20.1.0").unwrap();
+    assert!(Path::new(&filepath::join(&root, vec!["v20.1.0", "node"])).exists());
+    assert!(!Path::new(&filepath::join(&root, vec!["v20.1.0", MARKER])).exists());
+    assert!(!Path::new(&staging).exists());
+
+    // an interrupted download is removed, a complete one is finished
+    let partial = create(&root, "18.0.0").unwrap();
+    let complete = filepath::join(&root, vec![STAGING_DIR, "v16.0.0-1"]);
+    fs::create_dir_all(&complete).unwrap();
+    fs::write(filepath::join(&complete, vec![MARKER]), "v16.0.0").unwrap();
+    clean(&root);
+    assert!(!Path::new(&partial).exists());
+    assert!(!Path::new(&filepath::join(&root, vec!["v18.0.0"])).exists());
+    assert!(Path::new(&filepath::join(&root, vec!["v16.0.0"])).exists());
+    assert!(!Path::new(&filepath::join(&root, vec![STAGING_DIR])).exists());
+
+    // a complete second architecture is merged next to the installed one,
+    // one that has been installed since is dropped
+    fs::create_dir_all(filepath::join(&root, vec!["v20.1.0", "x64"])).unwrap();
+    for (pid, arch) in [("1", "arm64"), ("2", "x64")] {
+        let staged = filepath::join(&root, vec![STAGING_DIR, &format!("v20.1.0-{}", pid)]);
+        fs::create_dir_all(filepath::join(&staged, vec![arch, "bin"])).unwrap();
+        fs::write(filepath::join(&staged, vec![MARKER]), "v20.1.0").unwrap();
+    }
+    clean(&root);
+    assert!(Path::new(&filepath::join(&root, vec!["v20.1.0", "arm64", "bin"])).exists());
+    assert!(!Path::new(&filepath::join(&root, vec!["v20.1.0", "x64", "bin"])).exists());
+    assert!(!Path::new(&filepath::join(&root, vec![STAGING_DIR])).exists());
+
+    fs::remove_dir_all(&root).unwrap();
+}
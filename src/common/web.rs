@@ -1,23 +1,105 @@
-use crate::common::{arch, file};
-use reqwest::{Client, ClientBuilder, Proxy, StatusCode};
-use std::error::Error;
-use std::fs;
+use crate::common::arch::Arch;
+use crate::common::error::{NvmError, Result};
+use crate::common::{file, strings};
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy, StatusCode};
 use std::fs::File;
 use std::io::Write;
-use std::process::exit;
+use std::path::PathBuf;
 use std::{cmp::Ordering, path::MAIN_SEPARATOR};
+use std::{env, fs};
 use tokio::runtime::Runtime;
-use url::Url;
 
 #[derive(Debug)]
 pub struct WebContext {
     node_base_address: String,
     npm_base_address: String,
+    // "xz" or "gz", the archive downloaded on linux and macos
+    archive_format: String,
+    // "musl" downloads the -musl linux builds from the unofficial builds
+    libc: String,
     rt: Runtime,
     client: Client,
 }
 
+/// Certificate verification for the http client.
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    pub verify_ssl: bool,
+    /// PEM bundle of extra CA certificates
+    pub ca_file: String,
+    /// directory of .pem/.crt CA certificates
+    pub ca_dir: String,
+    /// also trust the system certificate store
+    pub ca_system: bool,
+}
+
+impl Default for TlsOptions {
+    fn default() -> Self {
+        TlsOptions {
+            verify_ssl: true,
+            ca_file: String::new(),
+            ca_dir: String::new(),
+            ca_system: false,
+        }
+    }
+}
+
+impl TlsOptions {
+    /// All the extra root certificates to add to the client.
+    pub fn certificates(&self) -> Result<Vec<Certificate>> {
+        let mut certs = Vec::new();
+        let mut files = Vec::new();
+        if !self.ca_file.is_empty() {
+            files.push(PathBuf::from(&self.ca_file));
+        }
+        if !self.ca_dir.is_empty() {
+            let entries = fs::read_dir(&self.ca_dir)
+                .map_err(|err| NvmError::io(&format!("read ca_dir {}", self.ca_dir), err))?;
+            let mut dir_files: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| {
+                    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+                    p.is_file() && (ext == "pem" || ext == "crt")
+                })
+                .collect();
+            dir_files.sort();
+            files.extend(dir_files);
+        }
+        for path in files {
+            let pem = fs::read(&path)
+                .map_err(|err| NvmError::io(&format!("read {}", path.display()), err))?;
+            let bundle = Certificate::from_pem_bundle(&pem).map_err(|err| {
+                NvmError::Config(format!(
+                    "{} is not a PEM certificate, {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+            if bundle.is_empty() {
+                return Err(NvmError::Config(format!(
+                    "no certificate found in {}",
+                    path.display()
+                )));
+            }
+            certs.extend(bundle);
+        }
+        if self.ca_system {
+            let native = rustls_native_certs::load_native_certs()
+                .map_err(|err| NvmError::io("load system certificates", err))?;
+            for cert in native {
+                let cert = Certificate::from_der(&cert.0).map_err(|err| {
+                    NvmError::Config(format!("invalid system certificate, {}", err))
+                })?;
+                certs.push(cert);
+            }
+        }
+        Ok(certs)
+    }
+}
+
 const NODE_BASE_ADDRESS: &str = "https://nodejs.org/dist/";
+// musl builds are only published by the unofficial builds project
+const UNOFFICIAL_BASE_ADDRESS: &str = "https://unofficial-builds.nodejs.org/download/release/";
 const NPM_BASE_ADDRESS: &str = "https://github.com/npm/cli/archive/";
 
 impl WebContext {
@@ -33,17 +115,31 @@ impl WebContext {
         WebContext {
             node_base_address: NODE_BASE_ADDRESS.to_owned(),
             npm_base_address: NPM_BASE_ADDRESS.to_owned(),
+            archive_format: "xz".to_string(),
+            libc: "glibc".to_string(),
             rt: rt.unwrap(),
             client: Client::new(),
         }
     }
 
+    pub fn set_archive_format(&mut self, format: &str) {
+        self.archive_format = if format == "gz" { "gz" } else { "xz" }.to_string();
+    }
+
+    /// Use the musl builds when `libc` is musl, from unofficial-builds unless
+    /// a node mirror is set.
+    pub fn set_libc(&mut self, libc: &str) {
+        self.libc = libc.to_string();
+        if libc == "musl" && self.node_base_address == NODE_BASE_ADDRESS {
+            self.node_base_address = UNOFFICIAL_BASE_ADDRESS.to_owned();
+        }
+    }
+
     pub fn set_mirrors(&mut self, node_mirror: &str, npm_mirror: &str) {
-        let mut node_base_address = String::new();
         if node_mirror != "" && node_mirror != "none" {
-            node_base_address = node_mirror.to_string();
+            let mut node_base_address = node_mirror.to_string();
 
-            if node_base_address.to_lowercase().starts_with("http") {
+            if !node_base_address.to_lowercase().starts_with("http") {
                 node_base_address = "http://".to_string() + node_base_address.as_ref();
             }
             if !node_base_address.ends_with("/") {
@@ -51,11 +147,10 @@ impl WebContext {
             }
             self.node_base_address = node_base_address;
         }
-        let mut npm_base_address: String = String::new();
         if npm_mirror != "" && npm_mirror != "none" {
-            npm_base_address = npm_mirror.to_string();
+            let mut npm_base_address = npm_mirror.to_string();
 
-            if npm_base_address.to_lowercase().starts_with("http") {
+            if !npm_base_address.to_lowercase().starts_with("http") {
                 npm_base_address = "http://".to_string() + npm_base_address.as_ref();
             }
             if !npm_base_address.ends_with("/") {
@@ -73,19 +168,58 @@ impl WebContext {
         self.npm_base_address.clone() + path
     }
 
-    pub fn set_proxy(&mut self, p: &str, verify_ssl: bool) -> Result<(), Box<dyn Error>> {
-        let mut builder = ClientBuilder::new().danger_accept_invalid_certs(!verify_ssl);
+    /// Build the http client. An explicit proxy is used for every request, otherwise
+    /// HTTPS_PROXY, HTTP_PROXY and ALL_PROXY are honored. Hosts in `no_proxy` and in
+    /// NO_PROXY always bypass the proxy. Extra CA certificates are trusted on top of
+    /// the built in roots.
+    pub fn set_proxy(&mut self, p: &str, no_proxy: &str, tls: &TlsOptions) -> Result<()> {
+        let mut builder = ClientBuilder::new().danger_accept_invalid_certs(!tls.verify_ssl);
+        for cert in tls.certificates()? {
+            builder = builder.add_root_certificate(cert);
+        }
+
+        let mut bypass: Vec<String> = no_proxy
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if let Some(env_bypass) = env_var(&["NO_PROXY", "no_proxy"]) {
+            bypass.push(env_bypass);
+        }
+        let bypass = NoProxy::from_string(&bypass.join(","));
 
         if !p.is_empty() && p != "none" {
-            let url = Url::parse(p)?;
-            let proxy = if p.starts_with("https") {
-                Proxy::https(url)?
-            } else {
-                Proxy::http(url)?
-            };
-            builder = builder.proxy(proxy);
+            let proxy = Proxy::all(p).map_err(|err| {
+                NvmError::Config(format!("invalid proxy {}, {}", strings::redact_url(p), err))
+            })?;
+            builder = builder.proxy(proxy.no_proxy(bypass));
+        } else {
+            let from_env = [
+                (["HTTPS_PROXY", "https_proxy"], "https"),
+                (["HTTP_PROXY", "http_proxy"], "http"),
+                (["ALL_PROXY", "all_proxy"], "all"),
+            ];
+            for (names, scheme) in from_env {
+                if let Some(url) = env_var(&names) {
+                    let proxy = match scheme {
+                        "https" => Proxy::https(&url),
+                        "http" => Proxy::http(&url),
+                        _ => Proxy::all(&url),
+                    }
+                    .map_err(|err| {
+                        NvmError::Config(format!(
+                            "invalid proxy {}, {}",
+                            strings::redact_url(&url),
+                            err
+                        ))
+                    })?;
+                    builder = builder.proxy(proxy.no_proxy(bypass.clone()));
+                }
+            }
         }
-        self.client = builder.build()?;
+        self.client = builder
+            .build()
+            .map_err(|err| NvmError::Config(format!("create http client fail, {}", err)))?;
         Ok(())
     }
 
@@ -102,121 +236,73 @@ impl WebContext {
         response.status().eq(&StatusCode::OK)
     }
 
-    pub fn download(&self, url: &str, target: &str, version: &str) -> bool {
-        let result = File::create(target);
-        if result.is_err() {
-            println!(
-                "errror: while creating:{}-{}",
-                target,
-                result.err().unwrap()
-            );
-            return false;
-        }
-
+    /// Download the url to the target file. A 404 is reported as not found and a
+    /// body shorter than its Content-Length as a checksum failure.
+    pub fn download(&self, url: &str, target: &str) -> Result<()> {
         let builder = self.client.get(url).header("User-Agent", "NVM WIN RUST");
-        let resp = self.rt.block_on(builder.send());
-
-        if resp.is_err() {
-            println!(
-                "errror: while downloading {} - {}",
-                url,
-                resp.err().unwrap()
-            );
-            return false;
-        }
-
-        let resp = resp.unwrap();
-        let headers = resp.headers().clone();
-        let status = resp.status().clone();
-        let response = self.rt.block_on(resp.bytes());
-
-        if response.is_err() {
-            println!("Http body read failed. {}", response.err().unwrap());
-            return false;
-        }
-
-        let mut file = result.unwrap();
-        let body = response.unwrap();
-
-        if let Some(err) = file.write_all(&body).err() {
-            println!("Failed to read response body: {}", err.to_string());
-            return false;
+        let resp = self
+            .rt
+            .block_on(builder.send())
+            .map_err(|err| NvmError::Network(format!("download {} fail, {}", url, err)))?;
+
+        let status = resp.status();
+        if status == StatusCode::MULTIPLE_CHOICES {
+            let redirect = resp
+                .headers()
+                .get("Location")
+                .and_then(|l| l.to_str().ok())
+                .unwrap_or("")
+                .to_string();
+            if !redirect.is_empty() && !redirect.eq(url) {
+                return self.download(&redirect, target);
+            }
+            if redirect.contains("/npm/cli/archive/v6.14.17.zip") {
+                return self.download(
+                    "https://github.com/npm/cli/archive/refs/tags/v6.14.17.zip",
+                    target,
+                );
+            }
         }
-
-        let redirect = headers.get("Location");
-        if redirect.is_some() {
-            let redirect_url = redirect.unwrap().to_str().unwrap().to_string();
-            match status {
-                StatusCode::MULTIPLE_CHOICES => {
-                    if redirect_url.len() > 0 && !redirect_url.eq(url) {
-                        let res = self.download(&redirect_url, target, version);
-                        return res;
-                    }
-
-                    if redirect_url.contains("/npm/cli/archive/v6.14.17.zip") {
-                        let url =
-                            "https://github.com/npm/cli/archive/refs/tags/v6.14.17.zip".to_string();
-                        let res = self.download(&url, target, version);
-                        return res;
-                    }
-                    println!(
-                        "\n\nRemote server failure\n\n---\nGet {} ---> {}\n\n",
-                        url, status
-                    );
-
-                    for (k, v) in headers.iter() {
-                        println!("{}: {}\n", k.to_string(), v.to_str().unwrap().to_string())
-                    }
-                    if body.len() > 0 {
-                        let str = String::from_utf8(body.to_vec()).unwrap();
-                        println!("\n{}", str)
-                    }
-                    println!("\n---\n\n");
-                    return false;
-                }
-                StatusCode::FOUND => {}
-                StatusCode::TEMPORARY_REDIRECT => {
-                    println!("Redirecting to {}", redirect_url);
-                    let res = self.download(&redirect_url, target, version);
-                    return res;
-                }
-                StatusCode::OK => {}
-                _ => {
-                    println!("Download failed. Rolling Back.");
-                    if let Err(err) = fs::remove_dir(target) {
-                        println!("{}", target);
-                        println!("Rollback failed. {}", err)
-                    }
-                    return false;
-                }
+        check_status(url, status)?;
+
+        let expected = resp.content_length();
+        let body = self
+            .rt
+            .block_on(resp.bytes())
+            .map_err(|err| NvmError::Network(format!("read {} fail, {}", url, err)))?;
+        if let Some(expected) = expected {
+            if body.len() as u64 != expected {
+                return Err(NvmError::Checksum(format!(
+                    "download of {} is incomplete, got {} of {} bytes",
+                    url,
+                    body.len(),
+                    expected
+                )));
             }
         }
-        true
-    }
 
-    pub fn get_remote_text_file(&self, url: &str) -> String {
-        let result = self.rt.block_on(self.client.get(url).send());
-        if result.is_err() {
-            println!("\nCould not retrieve {}.\n\n", url);
-            println!("{}", result.err().unwrap());
-            exit(1);
-        }
+        let mut file =
+            File::create(target).map_err(|err| NvmError::io(&format!("create {}", target), err))?;
+        file.write_all(&body)
+            .map_err(|err| NvmError::io(&format!("write {}", target), err))
+    }
 
-        let body = result.ok().unwrap();
-        let res = self.rt.block_on(body.bytes());
-        if res.is_err() {
-            println!("{}", res.err().unwrap());
-            exit(1);
-        }
-        let contents = String::from_utf8(res.unwrap().to_vec());
-        if contents.is_err() {
-            println!("{}", contents.err().unwrap());
-            exit(1);
-        }
-        contents.unwrap()
+    pub fn get_remote_text_file(&self, url: &str) -> Result<String> {
+        let resp = self
+            .rt
+            .block_on(self.client.get(url).send())
+            .map_err(|err| NvmError::Network(format!("could not retrieve {}, {}", url, err)))?;
+        check_status(url, resp.status())?;
+
+        let body = self
+            .rt
+            .block_on(resp.bytes())
+            .map_err(|err| NvmError::Network(format!("read {} fail, {}", url, err)))?;
+        String::from_utf8(body.to_vec())
+            .map_err(|err| NvmError::Network(format!("{} is not a text file, {}", url, err)))
     }
 
-    pub fn get_node_pre(v: &str) -> String {
+    pub fn get_node_pre(&self, v: &str, a: Arch) -> String {
         #[cfg(target_os = "windows")]
         let os_name = "win";
         #[cfg(target_os = "linux")]
@@ -226,88 +312,80 @@ impl WebContext {
 
         let mut main = 0;
         if let Some(main_str) = v.split(".").next() {
-            main = main_str.parse().unwrap();
+            main = main_str.parse().unwrap_or(0);
         }
 
-        let node_arch = arch::arch_map();
-
-        if main > 0 {
-            format!("{}-{}", os_name, node_arch)
+        if main > 0 && self.libc == "musl" {
+            format!("{}-{}-musl", os_name, a)
+        } else if main > 0 {
+            format!("{}-{}", os_name, a)
         } else {
             "".to_string()
         }
     }
 
-    pub fn get_node_js(&self, root: &str, v: &str, a: &str, append: bool) -> bool {
-        let v_pre = Self::get_node_pre(v);
+    /// Download node v into the version directory `dest` and extract it there.
+    pub fn get_node_js(&self, dest: &str, v: &str, a: Arch, append: bool) -> Result<()> {
+        let v_pre = self.get_node_pre(v, a);
 
-        let url = self.get_node_url(v, &v_pre, &a, append);
+        let url = self.get_node_url(v, &v_pre, a, append)?;
         if url.eq("") {
-            println!("Node.js v{} {} bit isn't available right now.", v, a);
-        } else {
-            #[cfg(target_os = "windows")]
-            let mut file_name = format!("{}\\v{}\\node{}.exe", root, v, a);
-            #[cfg(target_os = "windows")]
-            if url.ends_with(".zip") {
-                file_name = format!("{}\\v{}\\node.zip", root, v);
-            }
+            return Err(NvmError::NotFound(format!(
+                "Node.js v{} ({}) isn't available right now.",
+                v, a
+            )));
+        }
 
-            #[cfg(target_os = "linux")]
-            let mut file_name = format!("{}/v{}/node{}", root, v, a);
-            #[cfg(target_os = "linux")]
-            if url.ends_with(".tar.gz") {
-                file_name = format!("{}/v{}/node.tar.gz", root, v);
-            }
+        #[cfg(target_os = "windows")]
+        let mut file_name = format!("{}\\node{}.exe", dest, a.bits());
+        #[cfg(target_os = "windows")]
+        if url.ends_with(".zip") {
+            file_name = format!("{}\\node.zip", dest);
+        }
 
-            #[cfg(target_os = "macos")]
-            let mut file_name = format!("{}/v{}/node{}", root, v, a);
-            #[cfg(target_os = "macos")]
-            if url.ends_with(".tar.gz") {
-                file_name = format!("{}/v{}/node.tar.gz", root, v);
+        #[cfg(not(target_os = "windows"))]
+        let mut file_name = format!("{}/node{}", dest, a.bits());
+        #[cfg(not(target_os = "windows"))]
+        for ext in [".tar.xz", ".tar.gz"] {
+            if url.ends_with(ext) {
+                file_name = format!("{}/node{}", dest, ext);
             }
+        }
 
-            println!("Downloading node.js version {} ({}-bit)..", v, a);
-
-            return if self.download(&url, &file_name, v) {
-                #[cfg(target_os = "windows")]
-                let root_v = format!("{}\\v{}", root, v);
-                #[cfg(target_os = "linux")]
-                let root_v = format!("{}/v{}", root, v);
-                #[cfg(target_os = "macos")]
-                let root_v = format!("{}/v{}", root, v);
-
-                // Extract the zip file
-                if url.ends_with("zip") || url.ends_with("tar.gz") {
-                    println!("Extracting node and npm..");
+        println!("Downloading node.js version {} ({})..", v, a);
+        self.download(&url, &file_name)?;
 
-                    #[cfg(target_os = "windows")]
-                    let res = file::unzip(&file_name, &root_v, true);
+        // Extract the zip file
+        if url.ends_with("zip") || url.ends_with("tar.gz") || url.ends_with("tar.xz") {
+            println!("Extracting node and npm..");
 
-                    #[cfg(target_os = "linux")]
-                    let res = file::untar(&file_name, &root_v, true);
+            #[cfg(target_os = "windows")]
+            let res = file::unzip(&file_name, dest, true);
 
-                    #[cfg(target_os = "macos")]
-                    let res = file::untar(&file_name, &root_v, true);
+            #[cfg(not(target_os = "windows"))]
+            let res = file::untar(&file_name, dest, &format!("node-v{}-{}", v, v_pre));
 
-                    if fs::remove_file(&file_name).is_err() {
-                        println!("Failed to remove {} after successful extraction. Please remove manually.",file_name);
-                    }
+            if fs::remove_file(&file_name).is_err() {
+                println!(
+                    "Failed to remove {} after successful extraction. Please remove manually.",
+                    file_name
+                );
+            }
 
-                    if res.is_err() {
-                        println!("unzip or untar fail, err:{:?}", res.err());
-                        return false;
-                    }
-                }
-                println!("Complete");
-                true
-            } else {
-                false
-            };
+            res.map_err(|err| NvmError::io(&format!("extract {}", file_name), err))?;
         }
-        true
+        println!("Complete");
+        Ok(())
     }
 
-    pub fn get_npm(&self, root: &str, v: &str) -> bool {
+    /// Download the source tarball of node v to `target`.
+    pub fn get_node_source(&self, v: &str, target: &str) -> Result<()> {
+        let url = self.get_full_node_url(&format!("v{}/node-v{}.tar.gz", v, v));
+        println!("Downloading node.js v{} source..", v);
+        self.download(&url, target)
+    }
+
+    pub fn get_npm(&self, root: &str, v: &str) -> Result<()> {
         #[cfg(target_os = "windows")]
         let path = format!("v{}.zip", v);
 
@@ -322,11 +400,8 @@ impl WebContext {
         let temp_dir = format!("{}{}temp", root, MAIN_SEPARATOR);
         if !file::exists(&temp_dir) {
             println!("Creating {}\n", temp_dir);
-            let result = fs::create_dir(temp_dir.clone());
-            if result.is_err() {
-                println!("create npm temp dir failed.{}", result.err().unwrap());
-                exit(1);
-            }
+            fs::create_dir(&temp_dir)
+                .map_err(|err| NvmError::io(&format!("create npm temp dir {}", temp_dir), err))?;
         }
         #[cfg(target_os = "windows")]
         let file_name = format!("{}{}npm-v{}.zip", temp_dir, MAIN_SEPARATOR, v);
@@ -338,38 +413,33 @@ impl WebContext {
         let file_name = format!("{}{}npm-v{}.tar.gz", temp_dir, MAIN_SEPARATOR, v);
 
         println!("Downloading npm version {}...", v);
-        if self.download(&url, &file_name, v) {
-            println!("Complete\n");
-            return true;
-        } else {
-            false
-        }
+        self.download(&url, &file_name)?;
+        println!("Complete\n");
+        Ok(())
     }
 
-    pub fn get_node_url(&self, v: &str, v_pre: &str, arch: &str, append: bool) -> String {
+    pub fn get_node_url(&self, v: &str, v_pre: &str, arch: Arch, append: bool) -> Result<String> {
         let mut url = String::new();
         if !append {
-            let version = semver::Version::parse(&v);
-            if version.is_err() {
-                println!(
-                    "Node.js v{} {} bit isn't available right now.{}",
-                    v,
-                    arch,
-                    version.err().unwrap()
-                );
-                exit(1);
-            }
-            let version = version.unwrap();
+            let version = semver::Version::parse(v).map_err(|err| {
+                NvmError::Resolution(format!(
+                    "Node.js v{} ({}) isn't available right now, {}",
+                    v, arch, err
+                ))
+            })?;
 
             let core_pack = semver::Version::new(16, 9, 0);
 
             #[cfg(target_os = "windows")]
             let path = format!("v{}/node-v{}-{}.zip", v, v, v_pre);
-            #[cfg(target_os = "linux")]
-            let path = format!("v{}/node-v{}-{}.tar.gz", v, v, v_pre);
-
-            #[cfg(target_os = "macos")]
-            let path = format!("v{}/node-v{}-{}.tar.gz", v, v, v_pre);
+            #[cfg(not(target_os = "windows"))]
+            let path = format!(
+                "v{}/node-v{}-{}.{}",
+                v,
+                v,
+                v_pre,
+                self.archive_ext(v, v_pre)
+            );
 
             match version.cmp_precedence(&core_pack) {
                 Ordering::Equal => {
@@ -389,10 +459,27 @@ impl WebContext {
 
         #[cfg(target_os = "windows")]
         if let Err(err) = self.rt.block_on(self.client.head(&url).send()) {
-            println!("a 64 bit {} not exists,err:{}", v, err);
-            return "".to_string();
+            return Err(NvmError::Network(format!(
+                "check {} {} fail, {}",
+                arch, v, err
+            )));
         }
-        url
+        Ok(url)
+    }
+
+    // tar.xz when preferred and published for the version, tar.gz otherwise
+    #[cfg(not(target_os = "windows"))]
+    fn archive_ext(&self, v: &str, v_pre: &str) -> &'static str {
+        if self.archive_format == "xz" {
+            let url = self.get_full_node_url(&format!("v{}/SHASUMS256.txt", v));
+            let xz = format!("node-v{}-{}.tar.xz", v, v_pre);
+            if let Ok(shasums) = self.get_remote_text_file(&url) {
+                if shasums.lines().any(|line| line.ends_with(&xz)) {
+                    return "tar.xz";
+                }
+            }
+        }
+        "tar.gz"
     }
 
     pub fn is_node64_bid_available(v: &str) -> bool {
@@ -415,8 +502,61 @@ impl WebContext {
     }
 }
 
+// 404 means the mirror does not have the file, anything else unexpected is a network error
+fn check_status(url: &str, status: StatusCode) -> Result<()> {
+    if status == StatusCode::NOT_FOUND {
+        return Err(NvmError::NotFound(format!(
+            "{} was not found on the mirror",
+            url
+        )));
+    }
+    if !status.is_success() {
+        return Err(NvmError::Network(format!(
+            "get {} fail, the server answered {}",
+            url, status
+        )));
+    }
+    Ok(())
+}
+
+// the first of the variables that is set and not empty
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|v| !v.is_empty())
+}
+
 impl Default for WebContext {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_musl_mirror() {
+    let mut web_ctx = WebContext::new();
+    web_ctx.set_libc("musl");
+    assert_eq!(
+        web_ctx.get_full_node_url("index.json"),
+        format!("{}index.json", UNOFFICIAL_BASE_ADDRESS)
+    );
+
+    // a configured mirror is kept whichever is applied first
+    let mirror = "https://mirror.example.com/node/";
+    let mut web_ctx = WebContext::new();
+    web_ctx.set_mirrors(mirror, "");
+    web_ctx.set_libc("musl");
+    assert_eq!(
+        web_ctx.get_full_node_url("index.json"),
+        format!("{}index.json", mirror)
+    );
+    let mut web_ctx = WebContext::new();
+    web_ctx.set_libc("musl");
+    web_ctx.set_mirrors(mirror, "");
+    assert_eq!(
+        web_ctx.get_full_node_url("index.json"),
+        format!("{}index.json", mirror)
+    );
+}
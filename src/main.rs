@@ -2,13 +2,13 @@ use std::collections::HashMap;
 
 use std::{env, fs};
 
-use std::path::{PathBuf, MAIN_SEPARATOR};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
 use std::process::exit;
 use std::thread::sleep;
 use std::time::Duration;
 
-use common::node::{get_latest, get_lts};
 use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
 use dialoguer::Select;
 use regex::Regex;
 use semver::Version;
@@ -34,12 +34,25 @@ use winapi::um::processenv::GetStdHandle;
 #[cfg(target_os = "windows")]
 use winapi::um::winbase::STD_OUTPUT_HANDLE;
 
-use common::{arch, cmd, strings};
+use common::arch::{self, Arch};
+use common::{cmd, strings};
 
+#[cfg(not(target_os = "windows"))]
+use crate::common::checksum;
+use crate::common::config::{self, Config, Layers, Origin, Settings};
+use crate::common::error::{NvmError, Result};
 use crate::common::file;
 use crate::common::filepath;
+use crate::common::lock;
 use crate::common::node;
-use crate::common::web::WebContext;
+use crate::common::npm;
+use crate::common::schedule;
+#[cfg(not(target_os = "windows"))]
+use crate::common::shell::{self, Shell};
+use crate::common::staging;
+use crate::common::web::{TlsOptions, WebContext};
+#[cfg(not(target_os = "windows"))]
+use crate::common::{layout, link};
 
 mod common;
 
@@ -65,10 +78,22 @@ struct Environment {
     node_mirror: String,
     npm_mirror: String,
     proxy: String,
+    no_proxy: String,
     original_path: String,
     original_version: String,
     verify_ssl: bool,
+    ca_file: String,
+    ca_dir: String,
+    ca_system: bool,
+    // run corepack enable in every newly installed version
+    corepack: bool,
     version: String,
+    // settings given on the command line, such as --root
+    overrides: Vec<(String, String)>,
+    // per user install under $XDG_DATA_HOME, no system settings.txt
+    user_mode: bool,
+    // fail instead of waiting when another nvm process holds the root
+    no_wait: bool,
     ctx: Context,
 }
 
@@ -95,11 +120,19 @@ impl Environment {
             node_mirror: "".to_string(),
             npm_mirror: "".to_string(),
             proxy: "none".to_string(),
+            no_proxy: "".to_string(),
             original_path: "".to_string(),
             original_version: "".to_string(),
             verify_ssl: true,
+            ca_file: "".to_string(),
+            ca_dir: "".to_string(),
+            ca_system: false,
+            corepack: false,
             ctx: Context::new(),
             version: "1.0".to_string(),
+            overrides: vec![],
+            user_mode: false,
+            no_wait: false,
         }
     }
 
@@ -127,11 +160,19 @@ impl Environment {
             node_mirror: "".to_string(),
             npm_mirror: "".to_string(),
             proxy: "none".to_string(),
+            no_proxy: "".to_string(),
             original_path: "".to_string(),
             original_version: "".to_string(),
             verify_ssl: true,
+            ca_file: "".to_string(),
+            ca_dir: "".to_string(),
+            ca_system: false,
+            corepack: false,
             ctx: Context::new(),
             version: "1.0".to_string(),
+            overrides: vec![],
+            user_mode: false,
+            no_wait: false,
         }
     }
 
@@ -159,11 +200,19 @@ impl Environment {
             node_mirror: "".to_string(),
             npm_mirror: "".to_string(),
             proxy: "none".to_string(),
+            no_proxy: "".to_string(),
             original_path: "".to_string(),
             original_version: "".to_string(),
             verify_ssl: true,
+            ca_file: "".to_string(),
+            ca_dir: "".to_string(),
+            ca_system: false,
+            corepack: false,
             ctx: Context::new(),
             version: "1.0".to_string(),
+            overrides: vec![],
+            user_mode: false,
+            no_wait: false,
         }
     }
 }
@@ -171,9 +220,16 @@ impl Environment {
 fn main() {
     // 设置日志
     let mut nvm_env: Environment = Environment::new();
-    let args: Vec<String> = env::args().collect();
-    let mut detail = String::new();
-    let mut proc_arch = arch::validate(&nvm_env.arch);
+    let mut args: Vec<String> = env::args().collect();
+    nvm_env.overrides = config::take_flags(&mut args);
+    if args.iter().any(|a| a == "--insecure") {
+        nvm_env.verify_ssl = false;
+        args.retain(|a| a != "--insecure");
+    }
+    if args.iter().any(|a| a == "--no-wait") {
+        nvm_env.no_wait = true;
+        args.retain(|a| a != "--no-wait");
+    }
 
     #[cfg(target_os = "windows")]
     if !Environment::is_terminal() {
@@ -184,18 +240,32 @@ fn main() {
         exit(0);
     }
 
+    if let Err(err) = run(&mut nvm_env, &args) {
+        eprintln!("{}", err);
+        exit(err.exit_code());
+    }
+}
+
+fn run(nvm_env: &mut Environment, args: &[String]) -> Result<()> {
+    let mut detail = String::new();
+    // empty means the configured default
+    let mut proc_arch = String::new();
+
     if args.len() > 2 {
         detail = args[2].clone();
     }
 
     if args.len() > 3 {
-        if args[3].eq("32") || args[3].eq("64") {
+        if args[3].eq("all") || Arch::parse(&args[3]).is_some() {
             proc_arch = args[3].to_string();
         }
     }
+    if let Some(a) = arg_value(args, "--arch") {
+        proc_arch = a;
+    }
     if args.len() < 2 {
         help();
-        return;
+        return Ok(());
     }
 
     if args[1] != "version"
@@ -203,19 +273,60 @@ fn main() {
         && args[1] != "v"
         && args[1] != "-v"
         && args[1] != "--v"
+        && args[1] != "config"
+        && args[1] != "init"
     {
-        nvm_env.setup();
+        nvm_env.setup()?;
     }
 
     let cmd = &args[1];
     if cmd.is_empty() {
         help();
-        return;
+        return Ok(());
     }
+    let _lock = match lock_mode(args) {
+        Some(mode) => {
+            // versions installed before each architecture got its own dir are
+            // moved by commands that change the root, readers take them as is
+            #[cfg(not(target_os = "windows"))]
+            if mode == lock::Mode::Exclusive {
+                layout::migrate(&nvm_env.root, &nvm_env.symlink, !nvm_env.no_wait)?;
+            }
+            let root = if cmd == "config" {
+                nvm_env.config_root()
+            } else {
+                nvm_env.root.clone()
+            };
+            lock::acquire(&root, mode, !nvm_env.no_wait)?
+        }
+        None => None,
+    };
     let reload = vec![];
     match cmd.as_str() {
-        "install" => nvm_env.install(&detail, &proc_arch),
-        "uninstall" => nvm_env.uninstall(&detail),
+        "install" => {
+            let (version, installed) = if arg_value(args, "--from-file").is_some() {
+                nvm_env.install_from_file(&args[2..])?
+            } else if args.iter().any(|a| a == "--from-source") {
+                nvm_env.install_from_source(&detail, &args[2..])?
+            } else {
+                nvm_env.install(&detail, &proc_arch)?
+            };
+            nvm_env.after_install(&version, installed, &args[2..])
+        }
+        "uninstall" => {
+            let mut specs = positional(&args[2..], &["--arch"]);
+            // nvm uninstall <version> <arch>
+            if specs.len() == 2 && Arch::parse(&specs[1]).is_some() {
+                specs.pop();
+            }
+            let force = args.iter().any(|a| a == "--force");
+            nvm_env.uninstall(&specs, &proc_arch, force)
+        }
+        "prune" => nvm_env.prune(&args[2..]),
+        "schedule" => nvm_env.schedule(&args[2..]),
+        "audit" => nvm_env.audit(),
+        "upgrade" => nvm_env.upgrade(&args[2..]),
+        "reinstall-packages" => nvm_env.reinstall_packages_into_active(&detail),
         "switch" => nvm_env.switch(&detail),
         "sw" => nvm_env.switch(&detail),
         "use" => nvm_env.use_node(&detail, &proc_arch, &reload),
@@ -225,40 +336,41 @@ fn main() {
         "off" => nvm_env.disable(),
         "root" => {
             if args.len() == 3 {
-                nvm_env.update_root_dir(&args[2]);
+                nvm_env.update_root_dir(&args[2])
             } else {
                 println!("\nCurrent Root: {}", &nvm_env.root);
+                Ok(())
             }
         }
-        "v" => println!("{}", &nvm_env.version),
-        "--version" => println!("{}", &nvm_env.version),
-        "version" => println!("{}", &nvm_env.version),
-        "--v" => println!("{}", &nvm_env.version),
-        "-v" => println!("{}", &nvm_env.version),
+        "v" | "--version" | "version" | "--v" | "-v" => {
+            println!("{}", &nvm_env.version);
+            Ok(())
+        }
         "arch" => {
             let trim_c: &[_] = &['\r', '\n', ' '];
             detail = detail.trim_matches(trim_c).to_string();
             if !detail.is_empty() {
-                if detail != "32" && detail != "64" {
-                    println!("\"{}\" is an invalid architecture. Use 32 or 64.", detail);
-                    return;
-                }
-
-                nvm_env.arch = detail;
-                nvm_env.save_settings();
-                println!("Detault architecture set to {}-bit", nvm_env.arch);
+                nvm_env.arch = nvm_env.resolve_arch(&detail)?.to_string();
+                nvm_env.save_setting("arch", &nvm_env.arch)?;
+                println!("Detault architecture set to {}", nvm_env.arch);
             }
             let (_, a) = node::get_current_version();
-            println!("System Default: {}-bit.", nvm_env.arch);
-            println!("Currently Configured: {}-bit.", a);
+            println!("System Default: {}.", nvm_env.arch);
+            println!("Currently Configured: {}.", a);
+            Ok(())
         }
         "proxy" => {
             if detail == "" {
-                println!("Current proxy: {}", nvm_env.proxy);
+                println!("Current proxy: {}", strings::redact_url(&nvm_env.proxy));
+                if !nvm_env.no_proxy.is_empty() {
+                    println!("Bypassed hosts: {}", nvm_env.no_proxy);
+                }
             } else {
-                nvm_env.proxy = detail;
-                nvm_env.save_settings();
+                let proxy = config::validate("proxy", &detail).map_err(NvmError::Config)?;
+                nvm_env.save_setting("proxy", &proxy)?;
+                nvm_env.proxy = proxy;
             }
+            Ok(())
         }
         "current" => {
             let (in_use, _) = node::get_current_version();
@@ -271,10 +383,66 @@ fn main() {
             } else {
                 println!("v{}", in_use);
             }
+            Ok(())
         }
+        "config" => nvm_env.config(&args[2..]),
+        "init" => nvm_env.init(&args[2..]),
+        "implode" => nvm_env.implode(&args[2..]),
         "node_mirror" => nvm_env.set_node_mirror(&detail),
         "npm_mirror" => nvm_env.set_npm_mirror(&detail),
-        _ => help(),
+        _ => {
+            help();
+            Err(NvmError::Usage(format!("Unknown command \"{}\".", cmd)))
+        }
+    }
+}
+
+// the value of `--name value` or `--name=value`, empty when the value is missing
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix(&prefix) {
+            Some(value) => Some(value.to_string()),
+            None if arg == name => Some(args.get(i + 1).cloned().unwrap_or_default()),
+            None => None,
+        })
+}
+
+// the arguments that are neither flags nor the values of `flags`
+fn positional(args: &[String], flags: &[&str]) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut skip = false;
+    for arg in args {
+        if std::mem::take(&mut skip) {
+            continue;
+        }
+        if arg.starts_with("--") {
+            skip = flags.contains(&arg.as_str());
+            continue;
+        }
+        values.push(arg.clone());
+    }
+    values
+}
+
+// commands that change versions, the symlink or settings lock the root
+// exclusively, the ones that only read it share the lock
+fn lock_mode(args: &[String]) -> Option<lock::Mode> {
+    let arg = |i: usize| args.get(i).map(|a| a.as_str()).unwrap_or("");
+    match arg(1) {
+        "install" | "uninstall" | "prune" | "upgrade" | "reinstall-packages" | "switch" | "sw"
+        | "use" | "on" | "off" | "implode" | "node_mirror" | "npm_mirror" => {
+            Some(lock::Mode::Exclusive)
+        }
+        "root" | "arch" | "proxy" if !arg(2).is_empty() => Some(lock::Mode::Exclusive),
+        "config" if args.iter().skip(2).any(|a| a == "set" || a == "unset") => {
+            Some(lock::Mode::Exclusive)
+        }
+        "list" | "ls" | "current" | "root" | "arch" | "proxy" | "config" | "schedule" | "audit" => {
+            Some(lock::Mode::Shared)
+        }
+        _ => None,
     }
 }
 
@@ -282,80 +450,486 @@ fn help() {
     println!("\nRunning version 1.0 .");
     println!("\nUsage:");
     println!(" ");
-    println!("  nvm arch                     : Show if node is running in 32 or 64 bit mode.");
+    println!("  nvm audit                    : List installed versions that are missing security releases of their major line, and the");
+    println!("                                              patch to install. Exits with 10 when the version in use is one of them.");
+    println!("  nvm arch [arch]              : Show the architecture node is running as. Set [arch] to change the default architecture.");
+    println!("  nvm config list              : Show the settings. Use \"get <key>\", \"set <key> <value>\" or \"unset <key>\" to read or change one.");
+    println!("                                              Add --show-origin to see where each value came from, --system to write settings.txt.");
+    println!("                                              Passwords in urls are hidden, add --show-secrets to \"get\" to print them.");
+    println!("                                              Flags (--root, --symlink, --node-mirror, --npm-mirror, --proxy, --cacert, --mode, --libc) override the environment");
+    println!("                                              (NVM_HOME, NVM_SYMLINK, NVM_NODEJS_ORG_MIRROR, NVM_PROXY, NVM_MODE), then the user config, then settings.txt.");
     println!("  nvm current                  : Display active version.");
     println!("  nvm debug                    : Check the NVM4W process for known problems (troubleshooter).");
+    println!(
+        "  nvm init [--root DIR] [--symlink PATH] [--shell bash|zsh|fish] [--system] [--dry-run]"
+    );
+    println!("                               : Create the directories and config, and add nvm to PATH in the shell rc file.");
+    println!("                                              Without --system everything is kept under the home directory.");
+    println!("  nvm implode [--yes] [--keep-versions] : Uninstall nvm, removing versions, downloads, the symlink, settings and shell rc entries.");
     println!("  nvm install <version> [arch] : The version can be a specific version, \"latest\" for the latest current version, or \"lts\" for the");
-    println!("                                              most recent LTS version. Optionally specify whether to install the 32 or 64 bit version (defaults");
-    println!("                                              to system arch). Set [arch] to \"all\" to install 32 AND 64 bit versions.");
+    println!("                                              most recent LTS version. Optionally specify the architecture, x64, x86, arm64, armv7l,");
+    println!("                                              ppc64le, s390x or 32/64 (defaults to system arch), also as --arch <arch>.");
+    println!("                                              Set [arch] to \"all\" to install 32 AND 64 bit versions.");
+    println!("                                              Add --reinstall-packages-from=<version> to install the global npm packages of that version.");
+    println!("                                              A new version also gets the packages listed in {{root}}/default-packages, unless");
+    println!("                                              --skip-default-packages is given.");
+    println!("                                              Add --corepack (or set corepack to enable) to run corepack enable in the new version,");
+    println!("                                              and --package-manager <name@version> to prepare that pnpm or yarn as its default.");
     println!("                                              Add --insecure to the end of this command to bypass SSL validation of the remote download server.");
+    println!("                                              Prefer --cacert <file> (or the ca_file, ca_dir and ca_system settings) to trust a proxy's CA.");
+    println!(
+        "  nvm install --from-file <archive> [--version <version>] [--shasums <SHASUMS256.txt>]"
+    );
+    println!("                                              Install a downloaded node-v<version>-<os>-<arch>.tar.gz or .tar.xz, checking it");
+    println!("                                              against SHASUMS256.txt when given. The version is read from the file name by default.");
+    println!("  nvm install <version> --from-source [--source-dir <dir>] [--configure-flags \"<flags>\"] [--jobs <n>]");
+    println!("                                              Build node from the mirror's source tarball, or an existing source tree, with");
+    println!("                                              ./configure and make. The build output is logged to {{root}}/logs.");
     println!("  nvm list [available]         : List the node.js installations. Type \"available\" at the end to see what can be installed. Aliased as ls.");
+    println!("                                              Release lines in maintenance or past their end of life are marked.");
     println!("  nvm on                       : Enable node.js version management.");
     println!("  nvm off                      : Disable node.js version management.");
     println!("  nvm proxy [url]              : Set a proxy to use for downloads. Leave [url] blank to see the current proxy.");
     println!(
-        "                                               Set [url] to \"none\" to remove the proxy."
+        "                                               Set [url] to \"none\" to remove the proxy and use HTTP_PROXY/HTTPS_PROXY."
     );
+    println!("                                               http://, https:// and socks5:// proxies with user:pass@ are supported,");
+    println!("                                               \"nvm config set no_proxy host1,host2\" adds hosts that bypass it.");
     println!("  nvm node_mirror [url]        : Set the node mirror. Defaults to https://nodejs.org/dist/. Leave [url] blank to use default url.");
     println!("  nvm npm_mirror [url]         : Set the npm mirror. Defaults to https://github.com/npm/cli/archive/. Leave [url] blank to default url.");
-    println!("  nvm uninstall <version>      : The version must be a specific version.");
-    println!("  nvm switch [arch]            : Switch to use already install version. Optionally specify 32/64bit architecture. Aliased as sw.");
+    println!("  nvm uninstall <version>... [arch] : Remove versions, such as 16.20.2, every installed 14.x with 14, or a range like \">=12 <15\".");
+    println!("                                              Give [arch] to remove only that architecture of a single version.");
+    println!("                                              The version in use is kept unless it is named exactly or --force is given.");
+    println!("  nvm prune [--keep-latest-per-major] [--keep N] [--eol] [--dry-run] [--force]");
+    println!("                                              Remove installed versions, keeping the newest of each major line and/or the N newest,");
+    println!("                                              or only end-of-life ones with --eol. The version in use is kept unless --force is given.");
+    println!("  nvm switch [arch]            : Switch to use already install version. Optionally specify the architecture. Aliased as sw.");
     println!("  nvm use [version] [arch]     : Switch to use the specified version. Optionally use \"latest\", \"lts\", or \"newest\".");
-    println!("                                              \"newest\" is the latest installed version. Optionally specify 32/64bit architecture.");
-    println!("                                              nvm use <arch> will continue using the selected version, but switch to 32/64 bit mode.");
+    println!("                                              \"newest\" is the latest installed version. Optionally specify the architecture.");
+    println!("                                              nvm use <arch> will continue using the selected version, but switch to that architecture.");
+    println!("  nvm schedule [--all]         : Show the node.js release schedule. Add --all to include end-of-life lines.");
+    println!("  nvm upgrade <major>|--all [--reinstall-packages] [--yes]");
+    println!("                                              Install the newest patch of an installed major line, switch to it if the old one was");
+    println!("                                              in use, optionally move the global npm packages over, and offer to uninstall the old one.");
+    println!("  nvm reinstall-packages <version> : Install the global npm packages of <version> into the version in use,");
+    println!("                                              leaving out npm and corepack. Packages that fail are listed at the end.");
     println!("  nvm root [path]              : Set the directory where nvm should store different versions of node.js.");
     println!("                                              If <path> is not set, the current root will be displayed.");
     println!("  nvm [--]version              : Displays the current running version of nvm for Windows. Aliased as v.");
     println!(" ");
+    println!(
+        "  Commands wait while another nvm process installs, uninstalls or switches versions."
+    );
+    println!("  Add --no-wait to fail right away instead.");
+    println!(" ");
+    println!("Exit codes:");
+    println!(" ");
+    println!("  0 success, 1 other error, 2 invalid arguments, 3 bad settings, 4 network error,");
+    println!(
+        "  5 checksum mismatch, 6 not found, 7 permission denied, 8 version could not be resolved,"
+    );
+    println!("  9 another nvm process is busy with the root (--no-wait).");
+    println!(" ");
 }
 
 impl Environment {
-    fn set_node_mirror(&mut self, url: &str) {
+    fn set_node_mirror(&mut self, url: &str) -> Result<()> {
+        config::validate("node_mirror", url).map_err(NvmError::Config)?;
         self.node_mirror = url.to_string();
-        self.save_settings();
+        self.save_setting("node_mirror", url)
     }
 
-    fn set_npm_mirror(&mut self, url: &str) {
+    fn set_npm_mirror(&mut self, url: &str) -> Result<()> {
+        config::validate("npm_mirror", url).map_err(NvmError::Config)?;
         self.npm_mirror = url.to_string();
-        self.save_settings();
-    }
-
-    fn save_settings(&mut self) {
-        let mut content = String::new();
-        content.push_str(format!("root: {}\r\n", self.root.trim_end_matches("\r\n")).as_str());
-        content.push_str(format!("arch: {}\r\n", self.arch.trim_end_matches("\r\n")).as_str());
-        content.push_str(format!("proxy: {}\r\n", self.proxy.trim_end_matches("\r\n")).as_str());
-        content.push_str(
-            format!(
-                "original_path: {}\r\n",
-                self.original_path.trim_end_matches("\r\n")
-            )
-            .as_str(),
-        );
-        content.push_str(
-            format!(
-                "original_version: {}\r\n",
-                self.original_version.trim_end_matches("\r\n")
-            )
-            .as_str(),
-        );
-        content.push_str(
-            format!(
-                "node_mirror: {}\r\n",
-                self.node_mirror.trim_end_matches("\r\n")
-            )
-            .as_str(),
-        );
-        content.push_str(
-            format!(
-                "npm_mirror: {}\r\n",
-                self.npm_mirror.trim_end_matches("\r\n")
-            )
-            .as_str(),
-        );
-        if let Err(err) = fs::write(self.settings.as_str(), content.as_bytes()) {
-            println!("Save setting fail,err:{}", err)
+        self.save_setting("npm_mirror", url)
+    }
+
+    // nvm config list [--show-origin]|get <key> [--show-secrets]|set <key> <value>|unset <key> [--system]
+    fn config(&self, args: &[String]) -> Result<()> {
+        let show_origin = args.iter().any(|a| a == "--show-origin");
+        let show_secrets = args.iter().any(|a| a == "--show-secrets");
+        let system = args.iter().any(|a| a == "--system");
+        let args: Vec<&str> = args
+            .iter()
+            .map(|s| s.as_str())
+            .filter(|a| *a != "--show-origin" && *a != "--show-secrets" && *a != "--system")
+            .collect();
+
+        let layers = Layers::load(&self.settings, &self.overrides)
+            .map_err(|err| NvmError::from(err).context("Read settings"))?;
+        let path = config::write_path(&self.settings, system);
+        let mut config = match Config::load(&path) {
+            Ok(config) => config,
+            Err(_) if !file::exists(&path) => Config::default(),
+            Err(err) => {
+                return Err(NvmError::from(err).context(&format!("Read settings {}", path)));
+            }
+        };
+
+        let action = args.first().copied().unwrap_or("list");
+        let key = args.get(1).copied().unwrap_or("");
+        match action {
+            "list" | "ls" => {
+                for (key, value, origin) in layers.entries() {
+                    let mut line = format!("{}: {}", key, strings::redact_url(&value));
+                    if config::find_key(&key).is_none() {
+                        line.push_str(" (unknown key)");
+                    }
+                    if show_origin {
+                        line = format!("{:<48} {}", line, origin);
+                    }
+                    println!("{}", line);
+                }
+                return Ok(());
+            }
+            "get" => {
+                if key.is_empty() {
+                    return Err(NvmError::Usage("Usage: nvm config get <key>".to_string()));
+                }
+                match layers.get(key) {
+                    Some((value, origin)) => {
+                        // credentials in a proxy url stay hidden, as in list
+                        let value = if show_secrets {
+                            value
+                        } else {
+                            strings::redact_url(&value)
+                        };
+                        if show_origin {
+                            println!("{}\t{}", value, origin);
+                        } else {
+                            println!("{}", value);
+                        }
+                    }
+                    None if config::find_key(key).is_none() => {
+                        return Err(NvmError::Config(format!("Unknown config key \"{}\"", key)));
+                    }
+                    None => return Err(NvmError::Config(format!("{} is not set", key))),
+                }
+                return Ok(());
+            }
+            "set" => {
+                let value = match args.get(2) {
+                    Some(value) if !key.is_empty() => value,
+                    _ => {
+                        return Err(NvmError::Usage(
+                            "Usage: nvm config set <key> <value> [--system]".to_string(),
+                        ));
+                    }
+                };
+                let value = config::validate(key, value).map_err(NvmError::Config)?;
+                config.set(key, &value);
+            }
+            "unset" => {
+                if key.is_empty() {
+                    return Err(NvmError::Usage(
+                        "Usage: nvm config unset <key> [--system]".to_string(),
+                    ));
+                }
+                if !config.unset(key) {
+                    println!("{} is not set in {}", key, path);
+                    return Ok(());
+                }
+            }
+            _ => {
+                println!("Known keys:");
+                for key in config::KEYS {
+                    println!("  {:<18}: {}", key.name, key.description);
+                }
+                return Err(NvmError::Usage(format!(
+                    "Unknown config action \"{}\". Use list, get, set or unset.",
+                    action
+                )));
+            }
+        }
+
+        config
+            .save(&path)
+            .map_err(|err| NvmError::from(err).context(&format!("Save setting {}", path)))?;
+        if let Some((_, origin)) = layers.get(key) {
+            if matches!(origin, Origin::Flag(_) | Origin::Env(_)) {
+                println!("Note: {} is currently overridden by {}", key, origin);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn init(&self, _args: &[String]) -> Result<()> {
+        Err(NvmError::Usage(
+            "nvm init is not supported on Windows, please use the installer.".to_string(),
+        ))
+    }
+
+    // nvm init [--root DIR] [--symlink PATH] [--shell bash|zsh|fish] [--system] [--dry-run]
+    #[cfg(not(target_os = "windows"))]
+    fn init(&self, args: &[String]) -> Result<()> {
+        let dry_run = args.iter().any(|a| a == "--dry-run");
+        let system = args.iter().any(|a| a == "--system");
+        let mut shell = Shell::detect();
+        for (i, arg) in args.iter().enumerate() {
+            let name = match arg.strip_prefix("--shell=") {
+                Some(name) => name,
+                None if arg == "--shell" => args.get(i + 1).map(|s| s.as_str()).unwrap_or(""),
+                None => continue,
+            };
+            shell = Shell::parse(name).ok_or_else(|| {
+                NvmError::Usage(format!(
+                    "\"{}\" is not a supported shell. Use bash, zsh or fish.",
+                    name
+                ))
+            })?;
+        }
+
+        let mut exe_dir = file::get_executable_path()
+            .map_err(|err| NvmError::Other(format!("exec file path read fail, err:{:?}", err)))?;
+        exe_dir.pop();
+        let exe_dir = exe_dir.to_string_lossy().to_string();
+
+        let flag = |key: &str| {
+            self.overrides
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        };
+        let (root, symlink, config_path) = if system {
+            let root = flag("root").unwrap_or_else(|| exe_dir.clone());
+            let symlink = flag("symlink").unwrap_or_else(|| filepath::join(&root, vec!["node"]));
+            (root, symlink, self.settings.clone())
+        } else {
+            let (data_dir, config_path) =
+                match (config::user_data_dir(), config::user_config_path()) {
+                    (Some(data_dir), Some(config_path)) => (data_dir, config_path),
+                    _ => {
+                        return Err(NvmError::Config(
+                            "Could not find the home directory, use --system or set HOME."
+                                .to_string(),
+                        ));
+                    }
+                };
+            let data_dir = data_dir.to_string_lossy().to_string();
+            let root = flag("root").unwrap_or_else(|| filepath::join(&data_dir, vec!["versions"]));
+            let symlink =
+                flag("symlink").unwrap_or_else(|| filepath::join(&data_dir, vec!["current"]));
+            (root, symlink, config_path)
+        };
+
+        // directories
+        let mut dirs = vec![root.clone()];
+        if let Some(parent) = PathBuf::from(&symlink).parent() {
+            if !PathBuf::from(&root).starts_with(parent) {
+                dirs.push(parent.to_string_lossy().to_string());
+            }
+        }
+        for dir in dirs {
+            if file::exists(&dir) {
+                continue;
+            }
+            if dry_run {
+                println!("Would create {}", dir);
+            } else {
+                fs::create_dir_all(&dir)
+                    .map_err(|err| NvmError::io(&format!("Create {}", dir), err))?;
+                println!("Created {}", dir);
+            }
+        }
+
+        // config
+        let mut config = Config::load(&config_path).unwrap_or_default();
+        for (key, value) in [("root", &root), ("symlink", &symlink)] {
+            if !dry_run {
+                config::validate(key, value).map_err(NvmError::Config)?;
+            }
+            config.set(key, value);
+        }
+        if dry_run {
+            println!("\nWould write {}:\n{}", config_path, config.render());
+        } else {
+            config.save(&config_path).map_err(|err| {
+                NvmError::from(err).context(&format!("Save setting {}", config_path))
+            })?;
+            println!("Wrote {}", config_path);
+        }
+
+        // shell rc file
+        let rc_file = shell.rc_file().ok_or_else(|| {
+            NvmError::Config(format!(
+                "Could not find the {} rc file, set HOME.",
+                shell.name()
+            ))
+        })?;
+        let rc_path = rc_file.to_string_lossy().to_string();
+        let bin_dir = filepath::join(&symlink, vec!["bin"]);
+        let block = shell.block(&[&exe_dir, &bin_dir]);
+        let content = fs::read_to_string(&rc_file).unwrap_or_default();
+        let updated = shell::upsert_block(&content, &block);
+        if updated == content {
+            println!("{} is already up to date", rc_path);
+        } else if dry_run {
+            println!("Would update {}:\n{}", rc_path, block);
+        } else {
+            if let Some(parent) = rc_file.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            fs::write(&rc_file, updated)
+                .map_err(|err| NvmError::io(&format!("Update {}", rc_path), err))?;
+            println!("Updated {}", rc_path);
+        }
+
+        if !dry_run {
+            println!(
+                "\nRestart the shell or run \"source {}\" to start using nvm.",
+                rc_path
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn implode(&self, _args: &[String]) -> Result<()> {
+        Err(NvmError::Usage(
+            "nvm implode is not supported on Windows, please use the uninstaller.".to_string(),
+        ))
+    }
+
+    // nvm implode [--yes] [--keep-versions]
+    #[cfg(not(target_os = "windows"))]
+    fn implode(&self, args: &[String]) -> Result<()> {
+        let yes = args.iter().any(|a| a == "--yes" || a == "-y");
+        let keep_versions = args.iter().any(|a| a == "--keep-versions");
+
+        let mut dirs: Vec<String> = Vec::new();
+        let mut files: Vec<String> = Vec::new();
+        let mut rc_edits: Vec<(PathBuf, String)> = Vec::new();
+
+        if !keep_versions {
+            for v in node::get_installed(&self.root)? {
+                dirs.push(filepath::join(&self.root, vec![&v]));
+            }
+        }
+        for name in ["temp", "logs", schedule::CACHE_DIR, staging::STAGING_DIR] {
+            let dir = filepath::join(&self.root, vec![name]);
+            if file::exists(&dir) {
+                dirs.push(dir);
+            }
+        }
+        if fs::symlink_metadata(&self.symlink).is_ok() {
+            files.push(self.symlink.clone());
+        }
+        for rc_file in shell::rc_files() {
+            if let Ok(content) = fs::read_to_string(&rc_file) {
+                let stripped = shell::strip_nvm_lines(&content, &self.symlink);
+                if stripped != content {
+                    rc_edits.push((rc_file, stripped));
+                }
+            }
+        }
+        for path in [Some(self.settings.clone()), config::user_config_path()]
+            .into_iter()
+            .flatten()
+        {
+            if file::exists(&path) {
+                files.push(path);
+            }
+        }
+        let default_packages = filepath::join(&self.root, vec![npm::DEFAULT_PACKAGES_FILE]);
+        if file::exists(&default_packages) {
+            files.push(default_packages);
         }
+
+        if dirs.is_empty() && files.is_empty() && rc_edits.is_empty() {
+            println!("Nothing to remove.");
+            return Ok(());
+        }
+        println!("The following will be removed:\n");
+        for dir in &dirs {
+            println!("  {}", dir);
+        }
+        for file in &files {
+            println!("  {}", file);
+        }
+        for (rc_file, _) in &rc_edits {
+            println!("  nvm lines in {}", rc_file.to_string_lossy());
+        }
+        if keep_versions {
+            println!("\nInstalled versions in {} are kept.", self.root);
+        }
+        println!();
+
+        if !yes {
+            let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Uninstall nvm?")
+                .default(false)
+                .interact()
+                .unwrap_or(false);
+            if !confirmed {
+                println!("Aborted.");
+                return Ok(());
+            }
+        }
+
+        let mut failed = false;
+        for dir in &dirs {
+            if let Err(err) = fs::remove_dir_all(dir) {
+                println!("Remove {} fail, err:{}", dir, err);
+                failed = true;
+            }
+        }
+        for file in &files {
+            if let Err(err) = fs::remove_file(file) {
+                println!("Remove {} fail, err:{}", file, err);
+                failed = true;
+            }
+        }
+        for (rc_file, content) in &rc_edits {
+            if let Err(err) = fs::write(rc_file, content) {
+                println!("Update {} fail, err:{}", rc_file.to_string_lossy(), err);
+                failed = true;
+            }
+        }
+        // leave the root behind only when it still has something in it
+        if !keep_versions {
+            let _ = fs::remove_file(filepath::join(&self.root, vec![lock::LOCK_FILE]));
+            let _ = fs::remove_dir(&self.root);
+            if let Some(parent) = config::user_config_path()
+                .as_ref()
+                .and_then(|p| PathBuf::from(p).parent().map(|p| p.to_path_buf()))
+            {
+                let _ = fs::remove_dir(parent);
+            }
+            if self.user_mode {
+                if let Some(data_dir) = config::user_data_dir() {
+                    let _ = fs::remove_dir(data_dir);
+                }
+            }
+        }
+
+        if failed {
+            return Err(NvmError::Other(
+                "nvm was partly uninstalled, remove the paths above manually.".to_string(),
+            ));
+        }
+        println!("nvm has been uninstalled.");
+        if let Ok(exe) = file::get_executable_path() {
+            println!("Remove the executable {} to finish.", exe.to_string_lossy());
+        }
+        Ok(())
+    }
+
+    // write a single setting to the user config, or settings.txt where there is none
+    fn save_setting(&self, key: &str, value: &str) -> Result<()> {
+        let path = config::write_path(&self.settings, false);
+        let mut config = Config::load(&path).unwrap_or_default();
+        let value = value.trim_end_matches("\r\n");
+        if value.is_empty() {
+            config.unset(key);
+        } else {
+            config.set(key, value);
+        }
+        config
+            .save(&path)
+            .map_err(|err| NvmError::from(err).context(&format!("Save setting {}", path)))
     }
 
     #[cfg(target_os = "windows")]
@@ -405,76 +979,61 @@ impl Environment {
         version: &str,
         cpu_arch: &str,
         local_install_only: &Vec<bool>,
-    ) -> Result<(String, String), String> {
+    ) -> Result<(String, Vec<Arch>)> {
         let mut requested_version = version.to_string();
         let mut version = version.to_string();
-        let mut cpu_arch = cpu_arch.to_string();
-        requested_version = requested_version.to_uppercase();
-
-        if cpu_arch != "" {
-            if cpu_arch != "32" && cpu_arch != "64" && cpu_arch != "all" {
-                return Err(format!(
-                    "{} is not a valid CPU architecture. Must be 32 or 64.",
-                    cpu_arch
-                ));
-            }
+        // "all" is the 32 and 64 bit x86 builds side by side
+        let mut arches = if cpu_arch == "all" {
+            vec![Arch::X86, Arch::X64]
         } else {
-            cpu_arch = self.arch.to_string();
-        }
-
-        if cpu_arch != "all" {
-            cpu_arch = arch::validate(&cpu_arch);
-        }
+            vec![self.resolve_arch(cpu_arch)?]
+        };
+        requested_version = requested_version.to_uppercase();
 
         if version == "" {
-            return Err("A version argument is required but missing.".to_string());
+            return Err(NvmError::Usage(
+                "A version argument is required but missing.".to_string(),
+            ));
         }
 
         if version == "latest" || version == "node" {
-            let res = node::get_latest(&self.ctx.web_ctx);
-            if res.is_none() {
-                return Err("latest missing.".to_string());
-            }
-            version = res.unwrap();
+            version = node::get_latest(&self.ctx.web_ctx)?;
             println!("{}", version);
         }
 
         if version == "lts" {
-            let res = node::get_lts(&self.ctx.web_ctx);
-            if res.is_none() {
-                return Err("lts missing.".to_string());
-            }
-            version = res.unwrap();
+            version = node::get_lts(&self.ctx.web_ctx)?;
             println!("{}", version);
         }
 
         if version == "newest" {
-            let installed = node::get_installed(&self.root);
+            let installed = node::get_installed(&self.root)?;
             if installed.len() == 0 {
-                return Err("No versions of node.js found. Try installing the latest by typing nvm install latest.".to_string());
+                return Err(NvmError::NotFound("No versions of node.js found. Try installing the latest by typing nvm install latest.".to_string()));
             }
             version = installed.get(0).unwrap().to_string();
         }
 
-        if version == "32" || version == "64" {
-            cpu_arch = version.to_string();
+        // nvm use <arch> keeps the current version
+        if let Some(a) = Arch::parse(&version) {
+            arches = vec![a];
 
             let (v, _) = node::get_current_version();
             version = v;
         }
 
-        let mut version = self.version_number_from(version.as_str());
+        let mut version = self.version_number_from(version.as_str())?;
         let v = semver::Version::parse(&version);
         match v {
             Ok(_v) => {
                 let sv: Vec<&str> = version.split('.').collect();
                 if sv.len() < 3 {
-                    version = self.find_latest_sub_version(version.as_str(), &vec![]);
+                    version = self.find_latest_sub_version(version.as_str(), &vec![])?;
                 } else {
-                    version = self.clean_version(version.as_str());
+                    version = self.clean_version(version.as_str())?;
                 }
 
-                version = self.version_number_from(version.as_str());
+                version = self.version_number_from(version.as_str())?;
             }
             Err(err) => {
                 let err_str = err.to_string();
@@ -484,19 +1043,19 @@ impl Environment {
                         latest_local_install = local_install_only.get(0).unwrap().clone();
                     }
                     let tmp = vec![latest_local_install];
-                    version = self.find_latest_sub_version(version.as_str(), &tmp);
+                    version = self.find_latest_sub_version(version.as_str(), &tmp)?;
                     if version.len() == 0 {
                         let tmp = format!("Unrecognized version: \"{}\"", requested_version);
-                        return Err(tmp);
+                        return Err(NvmError::Resolution(tmp));
                     }
                 }
             }
         }
-        Ok((version, cpu_arch))
+        Ok((version, arches))
     }
 
     #[cfg(target_os = "windows")]
-    fn install(&mut self, version: &str, cpu_arch: &str) {
+    fn install(&mut self, version: &str, cpu_arch: &str) -> Result<(String, bool)> {
         let requested_version = version.to_string();
         let args: Vec<String> = env::args().collect();
         let last_arg = args.get(args.len() - 1).unwrap().clone();
@@ -511,232 +1070,71 @@ impl Environment {
             sleep(Duration::from_secs(2));
         }
 
-        let v_res = self.get_version(version, cpu_arch, &vec![]);
-        if v_res.is_err() {
-            let err = v_res.as_ref().err().unwrap();
-            if err.contains("No major.Minor.Patch") {
-                let sv_res = semver::Version::parse(version);
-                if sv_res.is_err() {
-                    let version = self.find_latest_sub_version(version, &vec![]);
-                    if version.len() == 0 {
-                        println!("Unrecognized version: \"{}\"", requested_version);
-                        if version == "" {
-                            println!(" ");
-                            help();
-                        }
-                        return;
-                    }
-                }
-            }
-        }
-        if v_res.is_err() {
-            println!("\"{}\" is not a valid version.", version);
-            println!("Please use a valid semantic version number, \"lts\", or \"latest\".");
-            return;
+        let (version, arches) = self.get_version(version, cpu_arch, &vec![])?;
+        if semver::Version::parse(&version).is_err() {
+            return Err(NvmError::Resolution(format!(
+                "\"{}\" is not a valid version.\nPlease use a valid semantic version number, \"lts\", or \"latest\".",
+                requested_version
+            )));
         }
+        let version = version.as_str();
 
-        if self.check_version_exceeds_latest(version) {
-            println!(
+        if self.check_version_exceeds_latest(version)? {
+            return Err(NvmError::Resolution(format!(
                 "Node.js v{} is not yet released or is not available.",
                 version
-            );
-            return;
+            )));
         }
 
-        if cpu_arch == "64" && !WebContext::is_node64_bid_available(version) {
-            println!("Node.js v{} is only available in 32-bit.", version);
-            return;
+        if arches.contains(&Arch::X64) && !WebContext::is_node64_bid_available(version) {
+            return Err(NvmError::NotFound(format!(
+                "Node.js v{} is only available in 32-bit.",
+                version
+            )));
         }
+        self.warn_eol(
+            version,
+            &schedule::load(&self.ctx.web_ctx, &self.root).unwrap_or_default(),
+        );
 
-        if !node::is_version_installed(&self.root, version, cpu_arch) {
-            if !node::is_version_available(version, &self.ctx.web_ctx) {
+        staging::clean(&self.root);
+        if !arches
+            .iter()
+            .all(|a| node::is_version_installed(&self.root, version, *a))
+        {
+            if !node::is_version_available(version, &self.ctx.web_ctx)? {
                 let url: String = self.ctx.web_ctx.get_full_node_url("index.json");
-                println!("\nVersion {} is not available. \n\nThe complete list of available version can be fount at {}", version, url);
-                return;
+                return Err(NvmError::NotFound(format!("Version {} is not available. \n\nThe complete list of available version can be fount at {}", version, url)));
             }
 
-            // Make the output directories
-            // node version dir
-            let mut node_dir_path = PathBuf::from(&self.root);
-            let version_name = format!("v{}", version);
-            node_dir_path.push(&version_name);
-            fs::create_dir_all(&node_dir_path).unwrap();
-
-            // node_modules
-            node_dir_path.push("node_modules");
-            fs::create_dir_all(&node_dir_path).unwrap();
-
             if !self.verify_ssl {
                 println!("\nWARNING: The remote SSL certificate will not be validated during the download process.\n");
+                println!("Use --cacert <file> or \"nvm config set ca_file <file>\" to trust a custom CA instead.\n");
             }
 
-            // Download node todo 疑惑点
-            let append32 = node::is_version_installed(&self.root, version, "64");
-            let append64 = node::is_version_installed(&self.root, version, "32");
-            if (cpu_arch == "32" || cpu_arch == "all")
-                && !node::is_version_installed(&self.root, version, "32")
-            {
-                let success = self
-                    .ctx
-                    .web_ctx
-                    .get_node_js(&self.root, version, "32", append32);
-                if !success {
-                    fs::remove_dir_all(&node_dir_path).unwrap();
-                    println!("Could not download node.js v{} 32-bit executable.", version);
-                    return;
-                }
-            }
-            if (cpu_arch == "64" || cpu_arch == "all")
-                && !node::is_version_installed(&self.root, version, "64")
-            {
-                let success = self
-                    .ctx
-                    .web_ctx
-                    .get_node_js(&self.root, version, "64", append64);
-                if !success {
-                    fs::remove_dir_all(&node_dir_path).unwrap();
-                    println!("Could not download node.js v{} 64-bit executable.", version);
-                    return;
-                }
-            }
-
-            node_dir_path.push("npm");
-            if file::exists(node_dir_path.to_str().unwrap()) {
-                let npm_v = node::get_npm_version(version, &self.ctx.web_ctx);
-                println!("npm v{} installed successfully.", npm_v);
-                println!("\n\n Installation complete. If you want to use this version, type\n\n nvm use {}", version);
-                return;
-            }
-            let npm_v = node::get_npm_version(version, &self.ctx.web_ctx);
-            let success = self.ctx.web_ctx.get_npm(&self.root, &npm_v);
-            if success {
-                println!("Installing npm v {}...", version);
-
-                let temp_dir = filepath::join(&self.root, vec!["temp"]);
-                let source_name = format!("npm-v{}.zip", npm_v);
-                let target_name = "nvm-npm";
-                let source_path = filepath::join(&temp_dir, vec![&source_name]);
-                let target_path = filepath::join(&temp_dir, vec![target_name]);
-
-                if let Err(err) = file::unzip(&source_path, &target_path, false) {
-                    fs::remove_dir_all(&node_dir_path).unwrap_or_else(|e| {
-                        println!(
-                            "Failed to remove directory where {} and err {}",
-                            node_dir_path.to_string_lossy().to_string(),
-                            e
-                        );
-                    });
-                    println!("Could not download npm {}: {}", npm_v, err);
-                    return;
-                }
-
-                let mut temp_npm_bin = filepath::join(
-                    &temp_dir,
-                    vec!["nvm-npm", format!("cli-{}", npm_v).as_str(), "bin"],
-                );
-                if !file::exists(&temp_npm_bin) {
-                    temp_npm_bin = filepath::join(
-                        &temp_dir,
-                        vec!["nvm-npm", format!("npm-{}", npm_v).as_str(), "bin"],
-                    );
-                }
-
-                if !file::exists(&temp_npm_bin) {
-                    println!("Failed to extract npm. Count not find {}", temp_npm_bin);
-                    exit(0);
-                }
-
-                let _ = fs::rename(
-                    filepath::join(&temp_npm_bin, vec!["npm"]),
-                    filepath::join(&self.root, vec![format!("v{}", version).as_str(), "npm"]),
-                );
-                let _ = fs::rename(
-                    filepath::join(&temp_npm_bin, vec!["npm.cmd"]),
-                    filepath::join(
-                        self.root.as_str(),
-                        vec![format!("v{}", version).as_str(), "npm.cmd"],
-                    ),
-                );
-
-                if file::exists(filepath::join(&temp_npm_bin, vec!["npx"]).as_str()) {
-                    let _ = fs::rename(
-                        filepath::join(&temp_npm_bin, vec!["npx"]),
-                        filepath::join(
-                            self.root.as_str(),
-                            vec![format!("v{}", version).as_str(), "npx"],
-                        ),
-                    );
-                    let _ = fs::rename(
-                        filepath::join(&temp_npm_bin, vec!["npx.cmd"]),
-                        filepath::join(
-                            self.root.as_str(),
-                            vec![format!("v{}", version).as_str(), "npx.cmd"],
-                        ),
-                    );
-                }
-
-                let mut npm_source_path = filepath::join(
-                    &temp_dir,
-                    vec!["nvm-npm", format!("npm-{}", npm_v).as_str()],
-                );
-                if !file::exists(&npm_source_path) {
-                    npm_source_path = filepath::join(
-                        &temp_dir,
-                        vec!["nvm-npm", format!("cli-{}", npm_v).as_str()],
-                    );
-                }
-
-                let move_npm_path = filepath::join(
-                    &self.root,
-                    vec![format!("v{}", version).as_str(), "node_modules", "npm"],
-                );
-                let mut move_npm_err = fs::rename(&npm_source_path, &move_npm_path);
-                if move_npm_err.is_err() {
-                    for i in [1, 2, 3, 8, 16] {
-                        sleep(Duration::from_secs(i as u64));
-                        move_npm_err = fs::rename(&npm_source_path, &move_npm_path);
-                        if move_npm_err.is_ok() {
-                            break;
-                        } else {
-                            println!(
-                                "Error: move {} to {} fail,err:{}",
-                                &npm_source_path,
-                                &move_npm_path,
-                                move_npm_err.as_ref().err().unwrap()
-                            );
-                        }
-                    }
-                }
-
-                if move_npm_err.is_ok() {
-                    fs::remove_dir_all(&temp_dir).unwrap();
-                    println!("\n\n Installation complete. If you want to use this version, type\n\n nvm use {}",version);
-                } else if move_npm_err.is_err() {
-                    println!("Error: Unable to move directory {}", npm_source_path);
-                } else {
-                    println!(
-                        "Error: Unable to install NPM: {}",
-                        move_npm_err.as_ref().err().unwrap()
-                    );
-                }
-            } else {
-                println!("Could not download npm for node v{}", version);
-                println!(
-                    "Please visit https://github.com/npm/cli/releases/tag/v{} to download npm.",
-                    npm_v
-                );
-                println!("It should be extracted to {} \\v{}", &self.root, version);
+            // everything is downloaded to a staging dir that only becomes
+            // {root}/v{version} once it is complete
+            let version_name = format!("v{}", version);
+            let staging_dir = staging::create(&self.root, version)?;
+            if let Err(err) = self.stage_node(&staging_dir, version, &arches) {
+                let _ = fs::remove_dir_all(&staging_dir);
+                return Err(err);
             }
+            staging::finish(&staging_dir, &self.root, &version_name)?;
+            println!(
+                "\n\n Installation complete. If you want to use this version, type\n\n nvm use {}",
+                version
+            );
             self.verify_ssl = true;
-            return;
+            Ok((version.to_string(), true))
         } else {
             println!("Version {} is already installed.", version);
-            return;
+            Ok((version.to_string(), false))
         }
     }
 
     #[cfg(target_os = "linux")]
-    fn install(&mut self, version: &str, cpu_arch: &str) {
+    fn install(&mut self, version: &str, cpu_arch: &str) -> Result<(String, bool)> {
         let requested_version = version.to_string();
         let args: Vec<String> = env::args().collect();
         let last_arg = args.get(args.len() - 1).unwrap().clone();
@@ -751,116 +1149,71 @@ impl Environment {
             sleep(Duration::from_secs(2));
         }
 
-        let v_res = self.get_version(version, cpu_arch, &vec![]);
-        if v_res.is_err() {
-            let err = v_res.as_ref().err().unwrap();
-            if err.contains("No major.Minor.Patch") {
-                let sv_res = semver::Version::parse(version);
-                if sv_res.is_err() {
-                    let version = self.find_latest_sub_version(version, &vec![]);
-                    if version.len() == 0 {
-                        println!("Unrecognized version: \"{}\"", requested_version);
-                        if version == "" {
-                            println!(" ");
-                            help();
-                        }
-                        return;
-                    }
-                }
-            }
-        }
-        if v_res.is_err() {
-            println!("\"{}\" is not a valid version.", version);
-            println!("Please use a valid semantic version number, \"lts\", or \"latest\".");
-            return;
+        let (version, arches) = self.get_version(version, cpu_arch, &vec![])?;
+        if semver::Version::parse(&version).is_err() {
+            return Err(NvmError::Resolution(format!(
+                "\"{}\" is not a valid version.\nPlease use a valid semantic version number, \"lts\", or \"latest\".",
+                requested_version
+            )));
         }
+        let version = version.as_str();
 
-        if self.check_version_exceeds_latest(version) {
-            println!(
+        if self.check_version_exceeds_latest(version)? {
+            return Err(NvmError::Resolution(format!(
                 "Node.js v{} is not yet released or is not available.",
                 version
-            );
-            return;
+            )));
         }
 
-        if cpu_arch == "64" && !WebContext::is_node64_bid_available(version) {
-            println!("Node.js v{} is only available in 32-bit.", version);
-            return;
+        if arches.contains(&Arch::X64) && !WebContext::is_node64_bid_available(version) {
+            return Err(NvmError::NotFound(format!(
+                "Node.js v{} is only available in 32-bit.",
+                version
+            )));
         }
+        self.warn_eol(
+            version,
+            &schedule::load(&self.ctx.web_ctx, &self.root).unwrap_or_default(),
+        );
 
-        if !node::is_version_installed(&self.root, version, cpu_arch) {
-            if !node::is_version_available(version, &self.ctx.web_ctx) {
+        staging::clean(&self.root);
+        if !arches
+            .iter()
+            .all(|a| node::is_version_installed(&self.root, version, *a))
+        {
+            if !node::is_version_available(version, &self.ctx.web_ctx)? {
                 let url: String = self.ctx.web_ctx.get_full_node_url("index.json");
-                println!("\nVersion {} is not available. \n\nThe complete list of available version can be fount at {}", version, url);
-                return;
+                return Err(NvmError::NotFound(format!("Version {} is not available. \n\nThe complete list of available version can be fount at {}", version, url)));
             }
 
-            // Make the output directories
-            // node version dir
-            let mut node_dir_path = PathBuf::from(&self.root);
-            let version_name = format!("v{}", version);
-            node_dir_path.push(&version_name);
-            fs::create_dir_all(&node_dir_path).unwrap();
-
-            // node_modules
-            node_dir_path.push("lib");
-            node_dir_path.push("node_modules");
-            fs::create_dir_all(&node_dir_path).unwrap();
-
             if !self.verify_ssl {
                 println!("\nWARNING: The remote SSL certificate will not be validated during the download process.\n");
+                println!("Use --cacert <file> or \"nvm config set ca_file <file>\" to trust a custom CA instead.\n");
             }
 
-            // Download node todo 疑惑点
-            let append32 = node::is_version_installed(&self.root, version, "64");
-            let append64 = node::is_version_installed(&self.root, version, "32");
-            if (cpu_arch == "32" || cpu_arch == "all")
-                && !node::is_version_installed(&self.root, version, "32")
-            {
-                let success = self
-                    .ctx
-                    .web_ctx
-                    .get_node_js(&self.root, version, "32", append32);
-                if !success {
-                    fs::remove_dir_all(&node_dir_path).unwrap();
-                    println!("Could not download node.js v{} 32-bit executable.", version);
-                    return;
-                }
-            }
-            if (cpu_arch == "64" || cpu_arch == "all")
-                && !node::is_version_installed(&self.root, version, "64")
-            {
-                let success = self
-                    .ctx
-                    .web_ctx
-                    .get_node_js(&self.root, version, "64", append64);
-                if !success {
-                    fs::remove_dir_all(&node_dir_path).unwrap();
-                    println!("Could not download node.js v{} 64-bit executable.", version);
-                    return;
-                }
-            }
-            node_dir_path.push("npm");
-
-            if file::exists(node_dir_path.to_str().unwrap()) {
-                let npm_v = node::get_npm_version(version, &self.ctx.web_ctx);
-                println!("npm v{} installed successfully.", npm_v);
-                println!("\n\n Installation complete. If you want to use this version, type\n\n nvm use {}", version);
-                return;
-            } else {
-                // 不再支持
-                println!("Node versions that are not bound to npm are no longer supported");
+            // everything is downloaded to a staging dir that only becomes
+            // {root}/v{version} once it is complete
+            let version_name = format!("v{}", version);
+            let staging_dir = staging::create(&self.root, version)?;
+            if let Err(err) = self.stage_node(&staging_dir, version, &arches) {
+                let _ = fs::remove_dir_all(&staging_dir);
+                return Err(err);
             }
+            staging::finish(&staging_dir, &self.root, &version_name)?;
+            println!(
+                "\n\n Installation complete. If you want to use this version, type\n\n nvm use {}",
+                version
+            );
             self.verify_ssl = true;
-            return;
+            Ok((version.to_string(), true))
         } else {
             println!("Version {} is already installed.", version);
-            return;
+            Ok((version.to_string(), false))
         }
     }
 
     #[cfg(target_os = "macos")]
-    fn install(&mut self, version: &str, cpu_arch: &str) {
+    fn install(&mut self, version: &str, cpu_arch: &str) -> Result<(String, bool)> {
         let requested_version = version.to_string();
         let args: Vec<String> = env::args().collect();
         let last_arg = args.get(args.len() - 1).unwrap().clone();
@@ -875,210 +1228,1188 @@ impl Environment {
             sleep(Duration::from_secs(2));
         }
 
-        let v_res = self.get_version(version, cpu_arch, &vec![]);
-        if v_res.is_err() {
-            let err = v_res.as_ref().err().unwrap();
-            if err.contains("No major.Minor.Patch") {
-                let sv_res = semver::Version::parse(version);
-                if sv_res.is_err() {
-                    let version = self.find_latest_sub_version(version, &vec![]);
-                    if version.len() == 0 {
-                        println!("Unrecognized version: \"{}\"", requested_version);
-                        if version == "" {
-                            println!(" ");
-                            help();
-                        }
-                        return;
-                    }
+        let (version, arches) = self.get_version(version, cpu_arch, &vec![])?;
+        if semver::Version::parse(&version).is_err() {
+            return Err(NvmError::Resolution(format!(
+                "\"{}\" is not a valid version.\nPlease use a valid semantic version number, \"lts\", or \"latest\".",
+                requested_version
+            )));
+        }
+        let version = version.as_str();
+
+        if self.check_version_exceeds_latest(version)? {
+            return Err(NvmError::Resolution(format!(
+                "Node.js v{} is not yet released or is not available.",
+                version
+            )));
+        }
+
+        if arches.contains(&Arch::X64) && !WebContext::is_node64_bid_available(version) {
+            return Err(NvmError::NotFound(format!(
+                "Node.js v{} is only available in 32-bit.",
+                version
+            )));
+        }
+        self.warn_eol(
+            version,
+            &schedule::load(&self.ctx.web_ctx, &self.root).unwrap_or_default(),
+        );
+
+        staging::clean(&self.root);
+        if !arches
+            .iter()
+            .all(|a| node::is_version_installed(&self.root, version, *a))
+        {
+            if !node::is_version_available(version, &self.ctx.web_ctx)? {
+                let url: String = self.ctx.web_ctx.get_full_node_url("index.json");
+                return Err(NvmError::NotFound(format!("Version {} is not available. \n\nThe complete list of available version can be fount at {}", version, url)));
+            }
+
+            if !self.verify_ssl {
+                println!("\nWARNING: The remote SSL certificate will not be validated during the download process.\n");
+                println!("Use --cacert <file> or \"nvm config set ca_file <file>\" to trust a custom CA instead.\n");
+            }
+
+            // everything is downloaded to a staging dir that only becomes
+            // {root}/v{version} once it is complete
+            let version_name = format!("v{}", version);
+            let staging_dir = staging::create(&self.root, version)?;
+            if let Err(err) = self.stage_node(&staging_dir, version, &arches) {
+                let _ = fs::remove_dir_all(&staging_dir);
+                return Err(err);
+            }
+            staging::finish(&staging_dir, &self.root, &version_name)?;
+            println!(
+                "\n\n Installation complete. If you want to use this version, type\n\n nvm use {}",
+                version
+            );
+            self.verify_ssl = true;
+            Ok((version.to_string(), true))
+        } else {
+            println!("Version {} is already installed.", version);
+            Ok((version.to_string(), false))
+        }
+    }
+
+    // download the node builds for the requested architectures into the staging dir
+    fn download_node(&self, staging_dir: &str, version: &str, arches: &[Arch]) -> Result<()> {
+        for a in arches {
+            if node::is_version_installed(&self.root, version, *a) {
+                continue;
+            }
+            // on windows a 32 bit build is added next to an installed 64 bit
+            // one, or the other way round
+            let append = cfg!(target_os = "windows")
+                && match a {
+                    Arch::X86 => node::is_version_installed(&self.root, version, Arch::X64),
+                    Arch::X64 => node::is_version_installed(&self.root, version, Arch::X86),
+                    _ => false,
+                };
+            let dest = node::arch_dir(staging_dir, *a);
+            fs::create_dir_all(&dest)
+                .map_err(|err| NvmError::io(&format!("Create {}", dest), err))?;
+            self.ctx
+                .web_ctx
+                .get_node_js(&dest, version, *a, append)
+                .map_err(|err| {
+                    err.context(&format!(
+                        "Could not download node.js v{} {} executable",
+                        version, a
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+
+    // stage node and npm, older releases ship without npm so it is fetched separately
+    #[cfg(target_os = "windows")]
+    fn stage_node(&self, staging_dir: &str, version: &str, arches: &[Arch]) -> Result<()> {
+        self.download_node(staging_dir, version, arches)?;
+
+        // npm is already there when another architecture of the version is installed
+        let v_version = format!("v{}", version);
+        let installed_npm = filepath::join(&self.root, vec![&v_version, "node_modules", "npm"]);
+        let move_npm_path = filepath::join(staging_dir, vec!["node_modules", "npm"]);
+        let npm_v = node::get_npm_version(version, &self.ctx.web_ctx)?;
+        if file::exists(&installed_npm) || file::exists(&move_npm_path) {
+            println!("npm v{} installed successfully.", npm_v);
+            return Ok(());
+        }
+
+        self.ctx.web_ctx.get_npm(&self.root, &npm_v).map_err(|err| {
+            err.context(&format!(
+                "Could not download npm for node v{}. Please visit https://github.com/npm/cli/releases/tag/v{} to download npm.\nIt should be extracted to {} \\v{}",
+                version, npm_v, &self.root, version
+            ))
+        })?;
+        println!("Installing npm v {}...", version);
+
+        let temp_dir = filepath::join(&self.root, vec!["temp"]);
+        let source_name = format!("npm-v{}.zip", npm_v);
+        let target_name = "nvm-npm";
+        let source_path = filepath::join(&temp_dir, vec![&source_name]);
+        let target_path = filepath::join(&temp_dir, vec![target_name]);
+
+        file::unzip(&source_path, &target_path, false)
+            .map_err(|err| NvmError::io(&format!("Could not extract npm {}", npm_v), err))?;
+
+        let mut temp_npm_bin = filepath::join(
+            &temp_dir,
+            vec!["nvm-npm", format!("cli-{}", npm_v).as_str(), "bin"],
+        );
+        if !file::exists(&temp_npm_bin) {
+            temp_npm_bin = filepath::join(
+                &temp_dir,
+                vec!["nvm-npm", format!("npm-{}", npm_v).as_str(), "bin"],
+            );
+        }
+
+        if !file::exists(&temp_npm_bin) {
+            return Err(NvmError::NotFound(format!(
+                "Failed to extract npm. Count not find {}",
+                temp_npm_bin
+            )));
+        }
+
+        for name in ["npm", "npm.cmd", "npx", "npx.cmd"] {
+            let source = filepath::join(&temp_npm_bin, vec![name]);
+            if file::exists(&source) {
+                let _ = fs::rename(source, filepath::join(staging_dir, vec![name]));
+            }
+        }
+
+        let mut npm_source_path = filepath::join(
+            &temp_dir,
+            vec!["nvm-npm", format!("npm-{}", npm_v).as_str()],
+        );
+        if !file::exists(&npm_source_path) {
+            npm_source_path = filepath::join(
+                &temp_dir,
+                vec!["nvm-npm", format!("cli-{}", npm_v).as_str()],
+            );
+        }
+
+        let node_modules = filepath::join(staging_dir, vec!["node_modules"]);
+        fs::create_dir_all(&node_modules)
+            .map_err(|err| NvmError::io(&format!("Create {}", node_modules), err))?;
+        let mut move_npm_err = fs::rename(&npm_source_path, &move_npm_path);
+        if move_npm_err.is_err() {
+            for i in [1, 2, 3, 8, 16] {
+                sleep(Duration::from_secs(i as u64));
+                move_npm_err = fs::rename(&npm_source_path, &move_npm_path);
+                if move_npm_err.is_ok() {
+                    break;
+                } else {
+                    println!(
+                        "Error: move {} to {} fail,err:{}",
+                        &npm_source_path,
+                        &move_npm_path,
+                        move_npm_err.as_ref().err().unwrap()
+                    );
                 }
             }
         }
-        if v_res.is_err() {
-            println!("\"{}\" is not a valid version.", version);
-            println!("Please use a valid semantic version number, \"lts\", or \"latest\".");
-            return;
+
+        if let Err(err) = move_npm_err {
+            return Err(NvmError::io(
+                &format!("Error: Unable to move directory {}", npm_source_path),
+                err,
+            ));
+        }
+        fs::remove_dir_all(&temp_dir)
+            .map_err(|err| NvmError::io(&format!("Remove {}", temp_dir), err))
+    }
+
+    // stage node, which must come with npm
+    #[cfg(not(target_os = "windows"))]
+    fn stage_node(&self, staging_dir: &str, version: &str, arches: &[Arch]) -> Result<()> {
+        self.download_node(staging_dir, version, arches)?;
+        for a in arches {
+            let arch_dir = node::arch_dir(staging_dir, *a);
+            if Path::new(&arch_dir).exists() {
+                Self::check_staged_npm(&arch_dir)?;
+            }
         }
+        let npm_v = node::get_npm_version(version, &self.ctx.web_ctx)?;
+        println!("npm v{} installed successfully.", npm_v);
+        Ok(())
+    }
 
-        if self.check_version_exceeds_latest(version) {
+    #[cfg(not(target_os = "windows"))]
+    fn check_staged_npm(staging_dir: &str) -> Result<()> {
+        let npm_dir = filepath::join(staging_dir, vec!["lib", "node_modules", "npm"]);
+        if !file::exists(&npm_dir) {
+            // 不再支持
+            return Err(NvmError::NotFound(
+                "Node versions that are not bound to npm are no longer supported".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn install_from_source(&self, _version: &str, _args: &[String]) -> Result<(String, bool)> {
+        Err(NvmError::Usage(
+            "nvm install --from-source is not supported on Windows.".to_string(),
+        ))
+    }
+
+    // nvm install <version> --from-source [--source-dir <dir>] [--configure-flags "<flags>"] [--jobs <n>]
+    #[cfg(not(target_os = "windows"))]
+    fn install_from_source(&self, version: &str, args: &[String]) -> Result<(String, bool)> {
+        if version.is_empty() || version.starts_with("--") {
+            return Err(NvmError::Usage(
+                "A version argument is required, e.g. nvm install 20.11.0 --from-source."
+                    .to_string(),
+            ));
+        }
+        let source_dir = arg_value(args, "--source-dir");
+        if let Some(dir) = &source_dir {
+            if !Path::new(dir).join("configure").is_file() {
+                return Err(NvmError::NotFound(format!(
+                    "{} is not a node.js source tree, it has no configure script.",
+                    dir
+                )));
+            }
+        }
+        // a local tree with an exact version needs no access to the mirror
+        let version = match semver::Version::parse(version.trim_start_matches('v')) {
+            Ok(v) if source_dir.is_some() => v.to_string(),
+            _ => self.get_version(version, "", &vec![])?.0,
+        };
+        let jobs = match arg_value(args, "--jobs") {
+            Some(jobs) => jobs
+                .parse::<usize>()
+                .ok()
+                .filter(|j| *j > 0)
+                .ok_or_else(|| {
+                    NvmError::Usage(format!("--jobs needs a number, got \"{}\".", jobs))
+                })?,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        let configure_flags = arg_value(args, "--configure-flags").unwrap_or_default();
+
+        staging::clean(&self.root);
+        // a source build is always for the host
+        if node::is_version_installed(&self.root, &version, Arch::host()) {
+            println!("Version {} is already installed.", version);
+            return Ok((version, false));
+        }
+
+        let temp_dir = filepath::join(&self.root, vec!["temp"]);
+        let build_dir = match &source_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                fs::create_dir_all(&temp_dir)
+                    .map_err(|err| NvmError::io(&format!("Create {}", temp_dir), err))?;
+                let tarball = filepath::join(&temp_dir, vec![&format!("node-v{}.tar.gz", version)]);
+                let build_dir = filepath::join(&temp_dir, vec![&format!("node-v{}", version)]);
+                self.ctx.web_ctx.get_node_source(&version, &tarball)?;
+                println!("Extracting the source..");
+                let _ = fs::remove_dir_all(&build_dir);
+                let res = file::untar(&tarball, &build_dir, &format!("node-v{}", version));
+                let _ = fs::remove_file(&tarball);
+                res.map_err(|err| NvmError::io(&format!("extract {}", tarball), err))?;
+                build_dir
+            }
+        };
+
+        let log_dir = filepath::join(&self.root, vec!["logs"]);
+        fs::create_dir_all(&log_dir)
+            .map_err(|err| NvmError::io(&format!("Create {}", log_dir), err))?;
+
+        // the build is configured for where it ends up, so that npm's prefix
+        // and process.config name {root}/v{version}/{arch}, and installed
+        // under a DESTDIR from which it is moved into the staging dir
+        let version_name = format!("v{}", version);
+        let final_dir = node::arch_dir(
+            &filepath::join(&self.root, vec![&version_name]),
+            Arch::host(),
+        );
+        let dest_dir = filepath::join(&temp_dir, vec![&format!("node-v{}-destdir", version)]);
+        let _ = fs::remove_dir_all(&dest_dir);
+        let staging_dir = staging::create(&self.root, &version)?;
+        let install_dir = node::arch_dir(&staging_dir, Arch::host());
+        let prefix = format!("--prefix={}", final_dir);
+        let jobs = format!("-j{}", jobs);
+        let destdir = format!("DESTDIR={}", dest_dir);
+        let mut configure_args = vec![prefix.as_str()];
+        configure_args.extend(configure_flags.split_whitespace());
+        let steps = [
+            ("configure", "./configure", configure_args),
+            (
+                "make",
+                "make",
+                vec![jobs.as_str(), "install", destdir.as_str()],
+            ),
+        ];
+        let mut built = Ok(());
+        for (step, program, step_args) in steps {
+            let log = filepath::join(&log_dir, vec![&format!("{}-{}.log", version_name, step)]);
+            let _ = fs::remove_file(&log);
             println!(
-                "Node.js v{} is not yet released or is not available.",
+                "Running {} {}, logging to {}..",
+                program,
+                step_args.join(" "),
+                log
+            );
+            built = match cmd::run_logged(program, &build_dir, &step_args, &log) {
+                Ok(status) if status.success() => Ok(()),
+                Ok(status) => Err(NvmError::Other(format!(
+                    "{} failed ({}), see {}",
+                    step, status, log
+                ))),
+                Err(err) => Err(NvmError::io(
+                    &format!("Run {} in {}", program, build_dir),
+                    err,
+                )),
+            };
+            if built.is_err() {
+                break;
+            }
+        }
+        let built = built
+            .and_then(|_| {
+                let installed = Path::new(&dest_dir).join(final_dir.trim_start_matches('/'));
+                fs::rename(&installed, &install_dir)
+                    .map_err(|err| NvmError::io(&format!("Move {}", installed.display()), err))
+            })
+            .and_then(|_| Self::check_staged_npm(&install_dir));
+        let _ = fs::remove_dir_all(&dest_dir);
+        if let Err(err) = built {
+            let _ = fs::remove_dir_all(&staging_dir);
+            return Err(err);
+        }
+        staging::finish(&staging_dir, &self.root, &version_name)?;
+        if source_dir.is_none() {
+            let _ = fs::remove_dir_all(&build_dir);
+        }
+        println!(
+            "\n\n Installation complete. If you want to use this version, type\n\n nvm use {}",
+            version
+        );
+        Ok((version, true))
+    }
+
+    #[cfg(target_os = "windows")]
+    fn install_from_file(&self, _args: &[String]) -> Result<(String, bool)> {
+        Err(NvmError::Usage(
+            "nvm install --from-file is not supported on Windows yet.".to_string(),
+        ))
+    }
+
+    // nvm install --from-file <archive> [--version <version>] [--shasums <file>]
+    #[cfg(not(target_os = "windows"))]
+    fn install_from_file(&self, args: &[String]) -> Result<(String, bool)> {
+        let archive = arg_value(args, "--from-file").unwrap_or_default();
+        if archive.is_empty() || archive.starts_with("--") {
+            return Err(NvmError::Usage(
+                "--from-file needs the path of a node .tar.gz or .tar.xz archive.".to_string(),
+            ));
+        }
+        if !Path::new(&archive).is_file() {
+            return Err(NvmError::NotFound(format!("{} does not exist.", archive)));
+        }
+        let file_name = Path::new(&archive)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stem = match [".tar.gz", ".tar.xz"]
+            .iter()
+            .find_map(|ext| file_name.strip_suffix(ext))
+        {
+            Some(stem) => stem,
+            None => {
+                return Err(NvmError::Usage(format!(
+                    "{} is not a .tar.gz or .tar.xz archive.",
+                    archive
+                )))
+            }
+        };
+
+        let version = match arg_value(args, "--version") {
+            Some(version) => version.trim_start_matches('v').to_string(),
+            None => Regex::new(r"^node-v(\d+\.\d+\.\d+)-")
+                .unwrap()
+                .captures(stem)
+                .map(|c| c[1].to_string())
+                .ok_or_else(|| {
+                    NvmError::Resolution(format!(
+                        "Could not tell the version from {}, add --version <version>.",
+                        file_name
+                    ))
+                })?,
+        };
+        if semver::Version::parse(&version).is_err() {
+            return Err(NvmError::Resolution(format!(
+                "\"{}\" is not a valid version.",
                 version
+            )));
+        }
+
+        if let Some(shasums) = arg_value(args, "--shasums") {
+            checksum::verify(&archive, &shasums)?;
+            println!("{} matches {}", file_name, shasums);
+        }
+
+        staging::clean(&self.root);
+        let arch = self.resolve_arch(&arg_value(args, "--arch").unwrap_or_default())?;
+        if node::is_version_installed(&self.root, &version, arch) {
+            println!("Version {} is already installed.", version);
+            return Ok((version, false));
+        }
+
+        // the archive has to be the build for this system, same as a download
+        let top = format!(
+            "node-v{}-{}",
+            version,
+            self.ctx.web_ctx.get_node_pre(&version, arch)
+        );
+        let version_name = format!("v{}", version);
+        let staging_dir = staging::create(&self.root, &version)?;
+        println!("Extracting {}..", archive);
+        let install_dir = node::arch_dir(&staging_dir, arch);
+        let staged = file::untar(&archive, &install_dir, &top)
+            .map_err(|err| NvmError::io(&format!("extract {}", archive), err))
+            .and_then(|_| Self::check_staged_npm(&install_dir));
+        if let Err(err) = staged {
+            let _ = fs::remove_dir_all(&staging_dir);
+            return Err(err);
+        }
+        staging::finish(&staging_dir, &self.root, &version_name)?;
+        println!(
+            "\n\n Installation complete. If you want to use this version, type\n\n nvm use {}",
+            version
+        );
+        Ok((version, true))
+    }
+
+    // nvm uninstall <version|range>... [arch] [--force], removes every
+    // installed architecture of the versions unless one is given. The version
+    // in use is only removed when it is named exactly or with --force
+    fn uninstall(&self, specs: &[String], cpu_arch: &str, force: bool) -> Result<()> {
+        if specs.is_empty() {
+            return Err(NvmError::Usage(
+                "Provide the version you want to uninstall.".to_string(),
+            ));
+        }
+        let only = match cpu_arch {
+            "" | "all" => None,
+            name => Some(self.resolve_arch(name)?),
+        };
+
+        // every spec has to match before anything is removed
+        let active = self.active_version().map(|v| v.to_string());
+        let mut versions: Vec<String> = Vec::new();
+        let mut kept: Option<String> = None;
+        for spec in specs {
+            let matched = self.installed_matching(spec)?;
+            if matched.is_empty() {
+                let name = match Version::parse(spec.trim_start_matches('v')) {
+                    Ok(v) => format!("v{}", v),
+                    Err(_) => format!("\"{}\"", spec),
+                };
+                return Err(NvmError::NotFound(format!(
+                    "node {} is not installed. Type \"nvm list\" to see what is installed",
+                    name
+                )));
+            }
+            let exact = Version::parse(spec.trim_start_matches('v')).is_ok();
+            for v in matched {
+                if !exact && !force && active.as_ref() == Some(&v) {
+                    kept = Some(v);
+                } else if !versions.contains(&v) {
+                    versions.push(v);
+                }
+            }
+        }
+        if let Some(v) = kept.filter(|v| !versions.contains(v)) {
+            println!("Keeping v{}, it is in use. Add --force to remove it.", v);
+        }
+        if versions.is_empty() {
+            println!("Nothing to uninstall.");
+            return Ok(());
+        }
+
+        self.check_removable(&versions)?;
+        let mut reclaimed = 0;
+        for v in &versions {
+            reclaimed += self.remove_version(v, only)?;
+        }
+        println!(
+            "Removed {} version{}, reclaimed {}.",
+            versions.len(),
+            if versions.len() == 1 { "" } else { "s" },
+            strings::format_size(reclaimed)
+        );
+        Ok(())
+    }
+
+    // the installed versions a version, alias or range names, newest first
+    fn installed_matching(&self, spec: &str) -> Result<Vec<String>> {
+        let installed: Vec<String> = node::get_installed(&self.root)?
+            .iter()
+            .map(|v| v[1..].to_string())
+            .collect();
+        let exact = match spec.to_lowercase().as_str() {
+            "latest" | "node" => Some(node::get_latest(&self.ctx.web_ctx)?),
+            "lts" => Some(node::get_lts(&self.ctx.web_ctx)?),
+            "newest" => Some(installed.first().cloned().ok_or_else(|| {
+                NvmError::NotFound("No version of node.js found. Try installing the latest by typing nvm install latest.".to_string())
+            })?),
+            _ => None,
+        };
+        let req = node::version_req(exact.as_deref().unwrap_or(spec)).ok_or_else(|| {
+            NvmError::Resolution(format!("\"{}\" is not a valid version or range.", spec))
+        })?;
+        Ok(installed
+            .into_iter()
+            .filter(|v| Version::parse(v).is_ok_and(|v| req.matches(&v)))
+            .collect())
+    }
+
+    // remove a version, or one architecture of it, and return the bytes freed
+    fn remove_version(&self, v: &str, only: Option<Arch>) -> Result<u64> {
+        let installed = node::installed_arches(&self.root, v);
+        if let Some(a) = only.filter(|a| !installed.contains(a)) {
+            return Err(self.not_installed(v, a));
+        }
+        let version_dir = filepath::join(&self.root, vec![&format!("v{}", v)]);
+        // the version dir goes with its last architecture
+        let only = only.filter(|_| installed.len() > 1);
+        let remove_path = match only {
+            Some(a) => {
+                println!("Uninstalling node v{} ({})...", v, a);
+                node::arch_dir(&version_dir, a)
+            }
+            None => {
+                println!("Uninstalling node v{}...", v);
+                version_dir
+            }
+        };
+        self.unlink_removed(v, only, &remove_path)?;
+        let size = file::dir_size(Path::new(&remove_path));
+        fs::remove_dir_all(&remove_path).map_err(|err| {
+            NvmError::io(
+                &format!(
+                    "Error removing node v{}, manually remove {}",
+                    v, remove_path
+                ),
+                err,
+            )
+        })?;
+        println!("done");
+        Ok(size)
+    }
+
+    // drop the symlink when it points at what is about to be removed
+    #[cfg(target_os = "windows")]
+    fn unlink_removed(&self, v: &str, only: Option<Arch>, _remove_path: &str) -> Result<()> {
+        let (cv, ca) = node::get_current_version();
+        if cv == v && only.is_none_or(|a| a.name() == ca) {
+            let arg = filepath::clean(&self.symlink);
+            cmd::elevated_run(&self.root, "rmdir", vec![&arg])
+                .map_err(|err| NvmError::Other(format!("elevated_run fail,err:{}", err)))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn unlink_removed(&self, _v: &str, _only: Option<Arch>, remove_path: &str) -> Result<()> {
+        let symlink = filepath::clean(&self.symlink);
+        if fs::read_link(&symlink).is_ok_and(|target| target.starts_with(remove_path)) {
+            fs::remove_file(&symlink)
+                .map_err(|err| NvmError::io(&format!("Remove {}", symlink), err))?;
+        }
+        Ok(())
+    }
+
+    // a batch is checked before anything goes, so that a removal that can
+    // not be done does not leave it half done
+    fn check_removable(&self, versions: &[String]) -> Result<()> {
+        let mut dirs = vec![PathBuf::from(&self.root)];
+        if self
+            .active_version()
+            .is_some_and(|a| versions.contains(&a.to_string()))
+        {
+            if let Some(parent) = Path::new(&self.symlink).parent() {
+                dirs.push(parent.to_path_buf());
+            }
+        }
+        for dir in dirs {
+            file::check_writable(&dir).map_err(|err| {
+                NvmError::io(
+                    &format!("Nothing was removed, {} is not writable", dir.display()),
+                    err,
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    // nvm prune [--keep-latest-per-major] [--keep N] [--eol] [--dry-run] [--force]
+    fn prune(&self, args: &[String]) -> Result<()> {
+        let keep_latest_per_major = args.iter().any(|a| a == "--keep-latest-per-major");
+        let eol = args.iter().any(|a| a == "--eol");
+        let dry_run = args.iter().any(|a| a == "--dry-run");
+        let force = args.iter().any(|a| a == "--force");
+        let keep =
+            match arg_value(args, "--keep") {
+                Some(n) => Some(n.parse::<usize>().map_err(|_| {
+                    NvmError::Usage(format!("--keep needs a number, got \"{}\".", n))
+                })?),
+                None => None,
+            };
+        if !keep_latest_per_major && keep.is_none() && !eol {
+            return Err(NvmError::Usage(
+                "Say what to prune with --keep-latest-per-major, --keep N or --eol.".to_string(),
+            ));
+        }
+
+        // newest first, so the ones kept are the first seen
+        let installed: Vec<Version> = node::get_installed(&self.root)?
+            .iter()
+            .filter_map(|v| Version::parse(&v[1..]).ok())
+            .collect();
+        let mut kept: Vec<&Version> = Vec::new();
+        if let Some(n) = keep {
+            kept.extend(installed.iter().take(n));
+        }
+        if keep_latest_per_major {
+            let mut lines: Vec<String> = Vec::new();
+            for v in &installed {
+                let line = schedule::line_name(v);
+                if !lines.contains(&line) {
+                    lines.push(line);
+                    kept.push(v);
+                }
+            }
+        }
+        let lines = if eol {
+            Some(schedule::load(&self.ctx.web_ctx, &self.root)?)
+        } else {
+            None
+        };
+        let today = chrono::Local::now().date_naive();
+        let active = self.active_version();
+
+        let mut remove: Vec<(&Version, u64)> = Vec::new();
+        for v in &installed {
+            if kept.contains(&v) {
+                continue;
+            }
+            // lines missing from the schedule are too new to be in it
+            if let Some(lines) = &lines {
+                if !schedule::find(lines, v).is_some_and(|l| l.is_eol(today)) {
+                    continue;
+                }
+            }
+            if active.as_ref() == Some(v) && !force {
+                println!("Keeping v{}, it is in use. Add --force to remove it.", v);
+                continue;
+            }
+            let dir = filepath::join(&self.root, vec![&format!("v{}", v)]);
+            remove.push((v, file::dir_size(Path::new(&dir))));
+        }
+
+        if remove.is_empty() {
+            println!("Nothing to prune.");
+            return Ok(());
+        }
+        let total: u64 = remove.iter().map(|(_, size)| size).sum();
+        if dry_run {
+            println!("Would remove:\n");
+            for (v, size) in &remove {
+                println!("  v{} ({})", v, strings::format_size(*size));
+            }
+            println!("\nWould reclaim {}.", strings::format_size(total));
+            return Ok(());
+        }
+        let versions: Vec<String> = remove.iter().map(|(v, _)| v.to_string()).collect();
+        self.check_removable(&versions)?;
+        for v in &versions {
+            self.remove_version(v, None)?;
+        }
+        println!(
+            "Removed {} version{}, reclaimed {}.",
+            remove.len(),
+            if remove.len() == 1 { "" } else { "s" },
+            strings::format_size(total)
+        );
+        Ok(())
+    }
+
+    // nvm upgrade <major>|--all [--reinstall-packages] [--yes]
+    fn upgrade(&mut self, args: &[String]) -> Result<()> {
+        let all = args.iter().any(|a| a == "--all");
+        let reinstall = args.iter().any(|a| a == "--reinstall-packages");
+        let yes = args.iter().any(|a| a == "--yes" || a == "-y");
+
+        // newest first
+        let installed: Vec<Version> = node::get_installed(&self.root)?
+            .iter()
+            .filter_map(|v| Version::parse(&v[1..]).ok())
+            .collect();
+        let mut lines: Vec<String> = Vec::new();
+        if all {
+            for v in &installed {
+                let line = schedule::line_name(v)[1..].to_string();
+                if !lines.contains(&line) {
+                    lines.push(line);
+                }
+            }
+        } else {
+            match positional(args, &[]).first() {
+                Some(line) => lines.push(line.trim_start_matches('v').to_string()),
+                None => {
+                    return Err(NvmError::Usage(
+                        "Provide the major version to upgrade, e.g. nvm upgrade 18, or --all."
+                            .to_string(),
+                    ))
+                }
+            }
+        }
+        if lines.is_empty() {
+            println!("No installations recognized.");
+            return Ok(());
+        }
+
+        let (available, _, _, _, _, _) = node::get_available(&self.ctx.web_ctx)?;
+        for line in &lines {
+            self.upgrade_line(line, &installed, &available, reinstall, yes)?;
+        }
+        Ok(())
+    }
+
+    // move the newest installed release of a line to the newest available one
+    fn upgrade_line(
+        &mut self,
+        line: &str,
+        installed: &[Version],
+        available: &[Version],
+        reinstall: bool,
+        yes: bool,
+    ) -> Result<()> {
+        let req = node::version_req(line).ok_or_else(|| {
+            NvmError::Resolution(format!("\"{}\" is not a valid major version.", line))
+        })?;
+        let old = installed.iter().find(|v| req.matches(v)).ok_or_else(|| {
+            NvmError::NotFound(format!(
+                "node {} is not installed. Type \"nvm install {}\" to install it.",
+                line, line
+            ))
+        })?;
+        let newest = available.iter().find(|v| req.matches(v)).ok_or_else(|| {
+            NvmError::Resolution(format!("No node {} release is available.", line))
+        })?;
+        if newest <= old {
+            println!(
+                "v{} is the newest {}.x release, nothing to upgrade.",
+                old, line
+            );
+            return Ok(());
+        }
+        let (old, newest) = (old.to_string(), newest.to_string());
+        println!("Upgrading node v{} to v{}..", old, newest);
+        for a in node::installed_arches(&self.root, &old) {
+            self.install(&newest, a.name())?;
+        }
+
+        if self.active_version().is_some_and(|v| v.to_string() == old) {
+            // the same architecture as before, the default when it can not be told
+            let a = fs::read_link(&self.symlink)
+                .ok()
+                .and_then(|target| Arch::parse(&target.file_name()?.to_string_lossy()))
+                .map(|a| a.name())
+                .unwrap_or_default();
+            self.use_node(&newest, a, &vec![])?;
+        }
+        if reinstall {
+            self.reinstall_packages(&old, &newest)?;
+        }
+
+        let remove = yes
+            || Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Uninstall node v{}?", old))
+                .default(false)
+                .interact()
+                .unwrap_or(false);
+        if remove {
+            self.remove_version(&old, None)?;
+        } else {
+            println!(
+                "node v{} is kept, type \"nvm uninstall {}\" to remove it later.",
+                old, old
             );
-            return;
         }
+        Ok(())
+    }
 
-        if cpu_arch == "64" && !WebContext::is_node64_bid_available(version) {
-            println!("Node.js v{} is only available in 32-bit.", version);
-            return;
+    // what nvm install does once the version is there, the default packages
+    // only go into a version this call installed. Every step runs, a failing
+    // one does not skip those after it, and the failures are reported together
+    fn after_install(&self, version: &str, installed: bool, args: &[String]) -> Result<()> {
+        let mut steps: Vec<Result<()>> = Vec::new();
+        if let Some(from) = arg_value(args, "--reinstall-packages-from") {
+            steps.push(
+                self.newest_installed(&from)
+                    .and_then(|from| self.reinstall_packages(&from, version)),
+            );
+        }
+        let package_manager = arg_value(args, "--package-manager");
+        let corepack = args.iter().any(|a| a == "--corepack") || installed && self.corepack;
+        if corepack || package_manager.is_some() {
+            steps.push(self.enable_corepack(version, package_manager.as_deref()));
+        }
+        if installed && !args.iter().any(|a| a == "--skip-default-packages") {
+            steps.push(self.install_default_packages(version));
         }
 
-        if !node::is_version_installed(&self.root, version, cpu_arch) {
-            if !node::is_version_available(version, &self.ctx.web_ctx) {
-                let url: String = self.ctx.web_ctx.get_full_node_url("index.json");
-                println!("\nVersion {} is not available. \n\nThe complete list of available version can be fount at {}", version, url);
-                return;
+        let mut failed: Vec<NvmError> = steps.into_iter().filter_map(|s| s.err()).collect();
+        if failed.len() > 1 {
+            let messages: Vec<String> = failed.iter().map(|err| format!("  {}", err)).collect();
+            return Err(NvmError::Other(format!(
+                "node v{} is installed, but {} of the steps after it failed:\n{}",
+                version,
+                failed.len(),
+                messages.join("\n")
+            )));
+        }
+        failed.pop().map_or(Ok(()), Err)
+    }
+
+    // corepack enable puts the pnpm and yarn shims into the bin dir of the
+    // version, and corepack prepare <name@version> --activate fetches the
+    // package manager and makes it the default one
+    fn enable_corepack(&self, version: &str, package_manager: Option<&str>) -> Result<()> {
+        if package_manager.is_some_and(|pm| pm.is_empty() || pm.starts_with("--")) {
+            return Err(NvmError::Usage(
+                "--package-manager needs a package manager, e.g. pnpm@9 or yarn@4.".to_string(),
+            ));
+        }
+        let install_dir = self.install_dir(version)?;
+        if !npm::has(&install_dir, "corepack") {
+            return Err(NvmError::NotFound(format!(
+                "node v{} does not ship corepack, it came with node 14.19 and 16.9.",
+                version
+            )));
+        }
+        let bin_dir = npm::bin_dir(&install_dir);
+        let mut steps = vec![vec!["enable", "--install-directory", &bin_dir]];
+        if let Some(pm) = package_manager {
+            steps.push(vec!["prepare", pm, "--activate"]);
+        }
+        for step in steps {
+            let command = format!("corepack {}", step.join(" "));
+            println!("Running {}..", command);
+            match npm::run(&install_dir, "corepack", &step) {
+                Ok(status) if status.success() => {}
+                Ok(status) => {
+                    return Err(NvmError::Other(format!(
+                        "node v{} is installed, but {} failed, {}",
+                        version, command, status
+                    )))
+                }
+                Err(err) => {
+                    return Err(NvmError::io(
+                        &format!("node v{} is installed, but {}", version, command),
+                        err,
+                    ))
+                }
             }
+        }
+        Ok(())
+    }
+
+    // the packages of {root}/default-packages, a failure leaves node installed
+    fn install_default_packages(&self, version: &str) -> Result<()> {
+        let packages = npm::default_packages(&self.root);
+        if packages.is_empty() {
+            return Ok(());
+        }
+        println!(
+            "Installing {} default packages into node v{}..",
+            packages.len(),
+            version
+        );
+        let failed = self.install_globals(version, &packages)?;
+        if !failed.is_empty() {
+            return Err(NvmError::Other(format!(
+                "node v{} is installed, but {} of {} default packages failed: {}",
+                version,
+                failed.len(),
+                packages.len(),
+                failed.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    // nvm reinstall-packages <version>, into the version in use
+    fn reinstall_packages_into_active(&self, from: &str) -> Result<()> {
+        let to = self.active_version().ok_or_else(|| {
+            NvmError::NotFound(
+                "No node.js version is in use. Type \"nvm use <version>\" first.".to_string(),
+            )
+        })?;
+        let from = self.newest_installed(from)?;
+        if from == to.to_string() {
+            return Err(NvmError::Usage(format!(
+                "node v{} is the version in use, give the version to take the packages from.",
+                from
+            )));
+        }
+        self.reinstall_packages(&from, &to.to_string())
+    }
 
-            // Make the output directories
-            // node version dir
-            let mut node_dir_path = PathBuf::from(&self.root);
-            let version_name = format!("v{}", version);
-            node_dir_path.push(&version_name);
-            fs::create_dir_all(&node_dir_path).unwrap();
+    // the newest installed version a version or range names
+    fn newest_installed(&self, spec: &str) -> Result<String> {
+        if spec.is_empty() {
+            return Err(NvmError::Usage(
+                "Provide the version to take the global packages from.".to_string(),
+            ));
+        }
+        self.installed_matching(spec)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| NvmError::NotFound(format!("node {} is not installed.", spec)))
+    }
 
-            // node_modules
-            node_dir_path.push("lib");
-            node_dir_path.push("node_modules");
-            fs::create_dir_all(&node_dir_path).unwrap();
+    // where a version is installed, the build of the default architecture
+    // when there is one
+    fn install_dir(&self, version: &str) -> Result<String> {
+        let installed = node::installed_arches(&self.root, version);
+        let default = self.resolve_arch("")?;
+        let a = if installed.contains(&default) {
+            default
+        } else {
+            *installed
+                .first()
+                .ok_or_else(|| NvmError::NotFound(format!("node v{} is not installed.", version)))?
+        };
+        let version_dir = filepath::join(&self.root, vec![&format!("v{}", version)]);
+        Ok(node::arch_dir(&version_dir, a))
+    }
 
-            if !self.verify_ssl {
-                println!("\nWARNING: The remote SSL certificate will not be validated during the download process.\n");
-            }
+    // install the global packages of one version with the npm of another,
+    // going on past the packages that fail and naming them at the end
+    fn reinstall_packages(&self, from: &str, to: &str) -> Result<()> {
+        let packages = npm::globals(&self.install_dir(from)?);
+        if packages.is_empty() {
+            println!("node v{} has no global packages to reinstall.", from);
+            return Ok(());
+        }
+        println!(
+            "Reinstalling {} global packages of node v{} into v{}..",
+            packages.len(),
+            from,
+            to
+        );
+        let failed = self.install_globals(to, &packages)?;
+        if !failed.is_empty() {
+            return Err(NvmError::Other(format!(
+                "Could not reinstall {} of {} global packages into node v{}: {}",
+                failed.len(),
+                packages.len(),
+                to,
+                failed.join(", ")
+            )));
+        }
+        Ok(())
+    }
 
-            // Download node todo 疑惑点
-            let append32 = node::is_version_installed(&self.root, version, "64");
-            let append64 = node::is_version_installed(&self.root, version, "32");
-            if (cpu_arch == "32" || cpu_arch == "all")
-                && !node::is_version_installed(&self.root, version, "32")
-            {
-                let success = self
-                    .ctx
-                    .web_ctx
-                    .get_node_js(&self.root, version, "32", append32);
-                if !success {
-                    fs::remove_dir_all(&node_dir_path).unwrap();
-                    println!("Could not download node.js v{} 32-bit executable.", version);
-                    return;
+    // npm install --global each package with the npm of a version, one at a
+    // time so a package that fails does not take the others with it, and
+    // give back the ones that failed
+    fn install_globals<'a>(&self, version: &str, packages: &'a [String]) -> Result<Vec<&'a str>> {
+        let install_dir = self.install_dir(version)?;
+        let mut failed = Vec::new();
+        for package in packages {
+            match npm::run(&install_dir, "npm", &["install", "--global", package]) {
+                Ok(status) if status.success() => println!("  {} installed", package),
+                Ok(status) => {
+                    println!("  {} failed, npm {}", package, status);
+                    failed.push(package.as_str());
                 }
-            }
-            if (cpu_arch == "64" || cpu_arch == "all")
-                && !node::is_version_installed(&self.root, version, "64")
-            {
-                let success = self
-                    .ctx
-                    .web_ctx
-                    .get_node_js(&self.root, version, "64", append64);
-                if !success {
-                    fs::remove_dir_all(&node_dir_path).unwrap();
-                    println!("Could not download node.js v{} 64-bit executable.", version);
-                    return;
+                Err(err) => {
+                    println!("  {} failed, {}", package, err);
+                    failed.push(package.as_str());
                 }
             }
-            node_dir_path.push("npm");
-
-            if file::exists(node_dir_path.to_str().unwrap()) {
-                let npm_v = node::get_npm_version(version, &self.ctx.web_ctx);
-                println!("npm v{} installed successfully.", npm_v);
-                println!("\n\n Installation complete. If you want to use this version, type\n\n nvm use {}", version);
-                return;
-            } else {
-                // 不再支持
-                println!("Node versions that are not bound to npm are no longer supported");
-            }
-            self.verify_ssl = true;
-            return;
-        } else {
-            println!("Version {} is already installed.", version);
-            return;
         }
+        Ok(failed)
     }
 
-    fn uninstall(&self, version: &str) {
-        let mut v: String = version.to_string();
-        if v.len() == 0 {
-            println!("Provide the version you want to uninstall.");
-            help();
-            return;
-        }
-
-        if v.to_lowercase() == "latest" || v.to_lowercase() == "node" {
-            v = get_latest(&self.ctx.web_ctx).unwrap();
-        } else if v.to_lowercase() == "lts" {
-            v = get_lts(&self.ctx.web_ctx).unwrap();
-        } else if v.to_lowercase() == "newest" {
-            let installed = node::get_installed(&self.root);
-            if installed.len() == 0 {
-                println!("No version of node.js found. Try installing the latest by typing nvm install latest.");
-                return;
+    // nvm audit, fails when the version in use is missing security releases
+    fn audit(&self) -> Result<()> {
+        let installed: Vec<Version> = node::get_installed(&self.root)?
+            .iter()
+            .filter_map(|v| Version::parse(&v[1..]).ok())
+            .collect();
+        if installed.is_empty() {
+            println!("No installations recognized.");
+            return Ok(());
+        }
+        let security = node::get_security_releases(&self.ctx.web_ctx)?;
+        let active = self.active_version();
+
+        let mut vulnerable = 0;
+        let mut active_fix = None;
+        println!();
+        for v in &installed {
+            let in_use = if active.as_ref() == Some(v) { "*" } else { " " };
+            let missed = node::missed_security_releases(v, &security);
+            if missed.is_empty() {
+                println!("{} {} is up to date with its security releases", in_use, v);
+                continue;
             }
-            v = installed[0].clone();
+            vulnerable += 1;
+            let names: Vec<String> = missed.iter().rev().map(|m| m.to_string()).collect();
+            println!(
+                "{} {} is missing security releases {}\n    nvm install {} to get the fixes",
+                in_use,
+                v,
+                names.join(", "),
+                missed[0]
+            );
+            if active.as_ref() == Some(v) {
+                active_fix = Some((v, missed[0].clone()));
+            }
+        }
+        println!(
+            "\n{} of {} installed versions are missing security releases.",
+            vulnerable,
+            installed.len()
+        );
+        match active_fix {
+            Some((v, fix)) => Err(NvmError::Vulnerable(format!(
+                "node v{} is in use and missing security releases, install v{} or newer.",
+                v, fix
+            ))),
+            None => Ok(()),
         }
+    }
 
-        v = self.clean_version(&v);
-        if node::is_version_installed(&self.root, &v, "32")
-            || node::is_version_installed(&self.root, &v, "64")
-        {
-            println!("Uninstalling node v{}...", &v);
-            let (cv, _) = node::get_current_version();
-            if cv == v {
-                let arg = filepath::clean(&self.symlink);
-                let res = cmd::elevated_run(&self.root, "rmdir", vec![&arg]);
-                if res.is_err() {
-                    println!("elevated_run fail,err:{}", res.as_ref().err().unwrap());
-                    return;
-                }
-            }
-            let remove_path = filepath::join(&self.root, vec![&format!("v{}", v)]);
-            let e = fs::remove_dir_all(&remove_path);
-            if e.is_err() {
-                println!("Error removing node v{}", v);
-                println!(
-                    "Manually remove {}.",
-                    filepath::join(&self.root, vec![&format!("v{}", v)])
-                );
-            } else {
-                println!("done");
-            }
-        } else {
+    // warn when the release line of the version no longer gets security fixes,
+    // staying quiet when the schedule can not be had
+    fn warn_eol(&self, version: &str, lines: &[schedule::Line]) {
+        let line = Version::parse(version)
+            .ok()
+            .and_then(|v| schedule::find(lines, &v).cloned());
+        let today = chrono::Local::now().date_naive();
+        if let Some(line) = line.filter(|l| l.is_eol(today)) {
             println!(
-                "node v{} is not installed. Type \"nvm list\" to see what is installed",
-                v
+                "\nWARNING: node {} reached its end of life on {} and no longer gets security fixes.\n",
+                line.name, line.end
             );
         }
-        return;
     }
 
-    fn check_version_exceeds_latest(&self, version: &str) -> bool {
-        let url = self.ctx.web_ctx.get_full_node_url("latest/SHASUMS256.txt");
-        let content = self.ctx.web_ctx.get_remote_text_file(&url);
-        let re = regex::Regex::new("node-v(.+)+msi").unwrap();
-        let reg = Regex::new("node-v|-[xa].+").unwrap();
-        let temp = re.find(&content).unwrap().as_str();
-        let latest = reg.replace_all(temp, "");
+    // nvm schedule [--all]
+    fn schedule(&self, args: &[String]) -> Result<()> {
+        let all = args.iter().any(|a| a == "--all");
+        let lines = schedule::load(&self.ctx.web_ctx, &self.root)?;
+        let today = chrono::Local::now().date_naive();
+        let date = |d: Option<chrono::NaiveDate>| d.map(|d| d.to_string()).unwrap_or_default();
+
+        let mut table = Table::new();
+        table.max_column_width = 40;
+        let header = [
+            "Line",
+            "Codename",
+            "Start",
+            "LTS",
+            "Maintenance",
+            "End",
+            "Status",
+        ];
+        table.add_row(Row::new(header.iter().map(|h| {
+            TableCell::builder(h)
+                .col_span(1)
+                .alignment(Alignment::Center)
+                .build()
+        })));
+        for line in lines.iter().rev() {
+            let phase = line.phase(today);
+            if phase == schedule::Phase::EndOfLife && !all {
+                continue;
+            }
+            let cells = [
+                line.name.clone(),
+                line.codename.clone(),
+                line.start.to_string(),
+                date(line.lts),
+                date(line.maintenance),
+                line.end.to_string(),
+                phase.name().to_string(),
+            ];
+            table.add_row(Row::new(cells.into_iter().map(|c| {
+                TableCell::builder(c)
+                    .col_span(1)
+                    .alignment(Alignment::Center)
+                    .build()
+            })));
+        }
+        println!("{}", table.render());
+        if !all {
+            println!("Add --all to include the release lines past their end of life.");
+        }
+        Ok(())
+    }
+
+    // the version the symlink points at, which need not be the node on PATH
+    fn active_version(&self) -> Option<Version> {
+        let target = fs::read_link(&self.symlink).ok()?;
+        target.components().rev().find_map(|c| {
+            let name = c.as_os_str().to_str()?;
+            Version::parse(name.strip_prefix('v')?).ok()
+        })
+    }
+
+    fn check_version_exceeds_latest(&self, version: &str) -> Result<bool> {
+        let latest = node::get_latest(&self.ctx.web_ctx)?;
         let v_arr: Vec<&str> = version.split(".").collect();
         let l_arr: Vec<&str> = latest.split(".").collect();
         for i in 0..l_arr.len() {
-            let lat = l_arr[i].parse::<i32>().unwrap();
-            let ver = v_arr[i].parse::<i32>().unwrap();
+            let lat = l_arr[i].parse::<i32>().unwrap_or(0);
+            let ver = v_arr
+                .get(i)
+                .and_then(|v| v.parse::<i32>().ok())
+                .unwrap_or(0);
             if ver < lat {
-                return false;
+                return Ok(false);
             } else if ver > lat {
-                return true;
+                return Ok(true);
             }
         }
-        return false;
+        Ok(false)
     }
 
-    fn clean_version(&self, version: &str) -> String {
-        let mut re = Regex::new("\\d+.\\d+.\\d+").unwrap();
-        let mut matched = re.find(version).unwrap().as_str().to_string();
-        if matched.len() == 0 {
-            re = Regex::new("\\d+.\\d+").unwrap();
-            matched = re.find(version).unwrap().as_str().to_string();
-            if matched.len() == 0 {
-                matched = format!("{}.0.0", version);
-            } else {
-                matched = format!("{}.0", version);
-            }
-            println!("{}", matched);
+    fn clean_version(&self, version: &str) -> Result<String> {
+        let re = Regex::new("\\d+.\\d+.\\d+").unwrap();
+        if let Some(matched) = re.find(version) {
+            return Ok(matched.as_str().to_string());
+        }
+        let re = Regex::new("\\d+.\\d+").unwrap();
+        if let Some(matched) = re.find(version) {
+            return Ok(format!("{}.0", matched.as_str()));
+        }
+        let re = Regex::new("\\d+").unwrap();
+        match re.find(version) {
+            Some(matched) => Ok(format!("{}.0.0", matched.as_str())),
+            None => Err(NvmError::Resolution(format!(
+                "\"{}\" is not a valid version.",
+                version
+            ))),
         }
-        matched.to_string()
     }
 
-    fn update_root_dir(&mut self, path: &str) {
+    fn update_root_dir(&mut self, path: &str) -> Result<()> {
         if !file::exists(path) {
-            println!("{} does not exist or could not be found.", path);
-            return;
+            return Err(NvmError::NotFound(format!(
+                "{} does not exist or could not be found.",
+                path
+            )));
         }
 
         let current_root = self.root.clone();
@@ -1086,56 +2417,46 @@ impl Environment {
 
         let cmd_source = format!("{}/elevate.cmd", current_root);
         let cmd_target = format!("{}/elevate.cmd", self.root);
-        let hard_link = fs::hard_link(filepath::clean(&cmd_source), filepath::clean(&cmd_target));
-        if hard_link.is_err() {
-            println!("Update root dir fail,err:{}", hard_link.err().unwrap());
-            return;
-        }
+        fs::hard_link(filepath::clean(&cmd_source), filepath::clean(&cmd_target))
+            .map_err(|err| NvmError::io("Update root dir fail", err))?;
 
         let vbs_source = format!("{}/elevate.vbs", current_root);
         let vbs_target = format!("{}/elevate.vbs", self.root);
-        let hard_link = fs::hard_link(filepath::clean(&vbs_source), filepath::clean(&vbs_target));
-        if hard_link.is_err() {
-            println!("Update root dir fail,err:{}", hard_link.err().unwrap());
-            return;
-        }
+        fs::hard_link(filepath::clean(&vbs_source), filepath::clean(&vbs_target))
+            .map_err(|err| NvmError::io("Update root dir fail", err))?;
 
-        self.save_settings();
+        self.save_setting("root", &self.root)?;
 
         if current_root != self.root {
             println!("\n Root has been change from {} to {}", current_root, path);
         }
+        Ok(())
     }
 
-    fn version_number_from(&self, version: &str) -> String {
+    fn version_number_from(&self, version: &str) -> Result<String> {
         let re = Regex::new("[^0-9]").unwrap();
 
-        if re.is_match(&version[0..version.len() - 2]) {
+        if version.len() > 2 && re.is_match(&version[0..version.len() - 2]) {
             if &version[0..1] == "v" {
                 let path = format!("latest-{}/SHASUMS256.txt", version);
                 let url = self.ctx.web_ctx.get_full_node_url(path.as_str());
-                let tmp = self.ctx.web_ctx.get_remote_text_file(&url);
+                let tmp = self.ctx.web_ctx.get_remote_text_file(&url)?;
                 let versions: Vec<&str> = tmp.split("\n").collect();
                 let content = versions.get(0).unwrap();
                 if content.contains("node") {
                     let parts: Vec<&str> = content.split("-").collect();
                     if parts.len() > 1 {
                         if &parts[1][0..1] == "v" {
-                            return parts[1][1..parts.len()].to_string();
+                            return Ok(parts[1][1..parts.len()].to_string());
                         }
                     }
                 }
-                println!("\"{}\" is not a valid version or known alias. \n", version);
-                println!("\n Available aliases: latest, node(latest), lts\nNamed releases (boron, dubnium, etc) are also supported.");
-                exit(0);
+                return Err(NvmError::Resolution(format!("\"{}\" is not a valid version or known alias. \n\n Available aliases: latest, node(latest), lts\nNamed releases (boron, dubnium, etc) are also supported.", version)));
             }
         }
-        let mut tmp = version.to_string();
-        while re.is_match(&tmp[0..1]) {
-            tmp = tmp[1..tmp.len()].to_owned();
-        }
+        let tmp = version.trim_start_matches(|c: char| !c.is_ascii_digit());
 
-        return tmp;
+        Ok(tmp.to_string())
     }
 
     fn split_version(&self, version: &str) -> HashMap<&str, i32> {
@@ -1152,21 +2473,21 @@ impl Environment {
         result
     }
 
-    fn find_latest_sub_version(&self, version: &str, local_only: &Vec<bool>) -> String {
+    fn find_latest_sub_version(&self, version: &str, local_only: &Vec<bool>) -> Result<String> {
         if local_only.len() > 0 && *local_only.get(0).unwrap() {
-            let installed = node::get_installed(&self.root);
+            let installed = node::get_installed(&self.root)?;
             let mut result = String::from("");
             for v in installed.into_iter() {
                 let prefix = format!("v{}", version);
                 if v.starts_with(&prefix) {
                     if result != "" {
                         let current = semver::Version::parse(
-                            self.version_number_from(result.as_str()).as_str(),
+                            self.version_number_from(result.as_str())?.as_str(),
                         )
-                        .unwrap();
+                        .map_err(|err| NvmError::Resolution(err.to_string()))?;
                         let next =
-                            semver::Version::parse(self.version_number_from(v.as_str()).as_str())
-                                .unwrap();
+                            semver::Version::parse(self.version_number_from(v.as_str())?.as_str())
+                                .map_err(|err| NvmError::Resolution(err.to_string()))?;
 
                         if current.lt(&next) {
                             result = v.clone();
@@ -1183,7 +2504,7 @@ impl Environment {
         }
         let tmp: Vec<&str> = version.split(",").collect();
         if tmp.len() == 2 {
-            let (all, _, _, _, _, _) = node::get_available(&self.ctx.web_ctx);
+            let (all, _, _, _, _, _) = node::get_available(&self.ctx.web_ctx)?;
             let tmp = version.to_owned() + ".0";
             let mut requested = self.split_version(tmp.as_str());
 
@@ -1204,26 +2525,59 @@ impl Environment {
                     break;
                 }
             }
-            return format!(
+            return Ok(format!(
                 "{}.{}.{}",
                 requested.get("major").unwrap(),
                 requested.get("minor").unwrap(),
                 requested.get("patch").unwrap()
-            );
+            ));
         }
         let path = format!("latest-v{}.x/SHASUMS256.txt", version);
         let url = self.ctx.web_ctx.get_full_node_url(path.as_str());
-        let content = self.ctx.web_ctx.get_remote_text_file(url.as_str());
+        let content = self.ctx.web_ctx.get_remote_text_file(url.as_str())?;
         let re = Regex::new("node-v(.+)+msi").unwrap();
         let reg = Regex::new("node-v|-[xa].+").unwrap();
-        let find_str = re.find(&content).unwrap().as_str();
+        let find_str = re
+            .find(&content)
+            .ok_or_else(|| NvmError::Resolution(format!("no release found in {}", url)))?
+            .as_str();
         let latest = reg.replace_all(find_str, "");
-        latest.to_string()
+        Ok(latest.to_string())
     }
 
-    fn access_denied(err: &str) -> bool {
-        println!("{}", err);
+    // the architecture asked for on the command line, or the configured default
+    fn resolve_arch(&self, cpu_arch: &str) -> Result<Arch> {
+        let name = if cpu_arch.is_empty() {
+            self.arch.as_str()
+        } else {
+            cpu_arch
+        };
+        Arch::parse(name).ok_or_else(|| {
+            NvmError::Usage(format!(
+                "\"{}\" is not a valid CPU architecture. Use one of {}, or 32/64.",
+                name,
+                Arch::names()
+            ))
+        })
+    }
+
+    // tells the user which other builds of the version are installed
+    fn not_installed(&self, version: &str, a: Arch) -> NvmError {
+        let mut msg = format!("node v{} ({}) is not installed.", version, a);
+        for other in Arch::ALL {
+            if other != a && node::is_version_installed(&self.root, version, other) {
+                msg.push_str(&format!(
+                    "\n Did you mean node v{} ({})?\n If so, type \"nvm use {} {}\" to use it.",
+                    version, other, version, other
+                ));
+            }
+        }
+        NvmError::NotFound(msg)
+    }
 
+    // points the user at the elevation help when the error is an access denial
+    #[cfg(target_os = "windows")]
+    fn access_denied(err: &str) -> bool {
         if err.to_ascii_lowercase().contains("access is denied") {
             println!("See https://bit.ly/nvm4w-help");
             return true;
@@ -1232,13 +2586,14 @@ impl Environment {
     }
 
     // 切换node版本
-    fn switch(&self, arch: &str) {
+    fn switch(&self, arch: &str) -> Result<()> {
         let (in_use, _) = node::get_current_version();
-        let mut installed_versions = node::get_installed(&self.root);
+        let mut installed_versions = node::get_installed(&self.root)?;
 
         if installed_versions.is_empty() {
-            println!("No installations recognized.");
-            return;
+            return Err(NvmError::NotFound(
+                "No installations recognized.".to_string(),
+            ));
         }
 
         installed_versions.sort_by(|a, b| {
@@ -1264,60 +2619,44 @@ impl Environment {
             .max_length(5)
             .items(&installed_versions[..])
             .interact()
-            .unwrap();
+            .map_err(|err| NvmError::Other(format!("Pick node fail, {}", err)))?;
 
         if selection != i {
             let reload = vec![];
             self.use_node(&installed_versions[selection][1..], arch, &reload)
         } else {
             println!(
-                "Now using node v{} ({}-bit)",
-                &installed_versions[selection], arch
+                "Now using node v{} ({})",
+                &installed_versions[selection],
+                self.resolve_arch(arch)?
             );
+            Ok(())
         }
     }
 
     #[cfg(target_os = "windows")]
-    fn use_node(&self, version: &str, cpu_arch: &str, reload: &Vec<bool>) {
+    fn use_node(&self, version: &str, cpu_arch: &str, reload: &Vec<bool>) -> Result<()> {
         let local_install_only = vec![true];
-        let res = self.get_version(version, cpu_arch, &local_install_only);
-
-        if res.is_err() {
-            let err = res.as_ref().err().unwrap();
-            if err.contains("No Major.Minor.Patch") {
-                println!("{}", err);
-                return;
-            }
-        }
-        let (version, cpu_arch) = res.unwrap();
+        let (version, arches) = self.get_version(version, cpu_arch, &local_install_only)?;
+        let a = arches[0];
 
-        if !node::is_version_installed(&self.root, &version, &cpu_arch) {
-            println!("node v{} ({}-bit) is not installed.", &version, &cpu_arch);
-            if cpu_arch == "32" {
-                if node::is_version_installed(&self.root, &version, "64") {
-                    println!("\n Did you mean node v{} (64-bit)?\n If so, type \"nvm use {} 64\" to use it.",&version,&version);
-                }
-            }
-            if cpu_arch == "64" {
-                if node::is_version_installed(&self.root, &version, &cpu_arch) {
-                    println!("\n Did you mean node v{} (32-bit)?\n If so, type \"nvm use {} 32\" to use it.",&version,&version);
-                }
-            }
-            return;
+        if !node::is_version_installed(&self.root, &version, a) {
+            return Err(self.not_installed(&version, a));
         }
 
         let symlink = filepath::clean(&self.symlink);
-        // Remove symlink if it already exists
+        // Windows has no atomic replace of a directory link, the old one is
+        // removed before mklink creates the new one
         if file::exists(&self.symlink) {
             let res = cmd::elevated_run(&self.root, "rmdir", vec![&symlink]);
-            if res.is_err() {
-                if Self::access_denied(res.as_ref().err().unwrap()) {
-                    return;
+            if let Err(err) = res {
+                if Self::access_denied(&err) {
+                    return Err(NvmError::Permission(err));
                 }
+                println!("{}", err);
             }
         }
 
-        let mut ok = true;
         let v_version = format!("v{}", version);
         let target_symlink = filepath::join(&self.root, vec![&v_version]);
         let res = cmd::elevated_run(&self.root, "mklink", vec!["/D", &symlink, &target_symlink]);
@@ -1330,9 +2669,8 @@ impl Environment {
                 let res =
                     cmd::elevated_run(&self.root, "mklink", vec!["/D", &symlink, &target_symlink]);
 
-                if res.is_err() {
-                    ok = false;
-                    println!("{}", err)
+                if let Err(err) = res {
+                    return Err(NvmError::Permission(err));
                 }
             } else if err.contains("file already exists") {
                 let res = cmd::elevated_run(&self.root, "rmdir", vec![&symlink]);
@@ -1341,128 +2679,19 @@ impl Environment {
                     reloadable = reload[0];
                 }
 
-                if res.is_err() {
-                    println!("{}", res.as_ref().err().unwrap());
-                } else if reloadable {
-                    let reload = vec![false];
-                    self.use_node(&version, &cpu_arch, &reload);
-                    return;
-                }
-            } else {
-                println!("{}", err);
-            }
-        }
-
-        if !ok {
-            return;
-        }
-
-        // Use the assigned CPu architechture
-        let cpu_arch = arch::validate(&cpu_arch);
-        let node_path = filepath::join(&self.root, vec![&v_version, "node.exe"]);
-        let node32_path = filepath::join(&self.root, vec![&v_version, "node32.exe"]);
-        let node64_path = filepath::join(&self.root, vec![&v_version, "node64.exe"]);
-        let node_exists = file::exists(&node_path);
-        let node32_exists = file::exists(&node32_path);
-        let node64_exists = file::exists(&node64_path);
-
-        if node32_exists && cpu_arch == "32" {
-            if node_exists {
-                let _ = fs::rename(&node_path, &node64_path);
-            }
-            let _ = fs::rename(&node32_path, &node32_path);
-        }
-        if node64_exists && cpu_arch == "64" {
-            if node_exists {
-                let _ = fs::rename(&node_path, &node32_path);
-            }
-            let _ = fs::rename(node64_path, &node_path);
-        }
-        println!("Now using node v{} ({}-bit)", version, cpu_arch);
-    }
-
-    #[cfg(target_os = "linux")]
-    fn use_node(&self, version: &str, cpu_arch: &str, reload: &Vec<bool>) {
-        let local_install_only = vec![true];
-        let res = self.get_version(version, cpu_arch, &local_install_only);
-
-        if res.is_err() {
-            let err = res.as_ref().err().unwrap();
-            if err.contains("No Major.Minor.Patch") {
-                println!("{}", err);
-                return;
-            }
-        }
-        let (version, cpu_arch) = res.unwrap();
-
-        if !node::is_version_installed(&self.root, &version, &cpu_arch) {
-            println!("node v{} ({}-bit) is not installed.", &version, &cpu_arch);
-            if cpu_arch == "32" {
-                if node::is_version_installed(&self.root, &version, "64") {
-                    println!("\n Did you mean node v{} (64-bit)?\n If so, type \"nvm use {} 64\" to use it.",&version,&version);
-                }
-            }
-            if cpu_arch == "64" {
-                if node::is_version_installed(&self.root, &version, &cpu_arch) {
-                    println!("\n Did you mean node v{} (32-bit)?\n If so, type \"nvm use {} 32\" to use it.",&version,&version);
-                }
-            }
-            return;
-        }
-
-        let symlink = filepath::clean(&self.symlink);
-        // Remove symlink if it already exists
-        if file::exists(&self.symlink) {
-            let res = fs::remove_file(&symlink);
-            if res.is_err() {
-                if Self::access_denied(&res.as_ref().err().unwrap().to_string()) {
-                    return;
-                }
-            }
-        }
-
-        let mut ok = true;
-        let v_version = format!("v{}", version);
-        let target_symlink = filepath::join(&self.root, vec![&v_version]);
-        let res = std::os::unix::fs::symlink(&target_symlink, &symlink);
-
-        if res.is_err() {
-            let err = res.as_ref().err().unwrap().to_string();
-            if err.contains("not have sufficient privilege")
-                || err.to_lowercase().contains("access is denied")
-            {
-                let res = std::os::unix::fs::symlink(&target_symlink, &symlink);
-
-                if res.is_err() {
-                    ok = false;
-                    println!("{}", err)
-                }
-            } else if err.contains("file already exists") {
-                let res = fs::remove_file(&symlink);
-                let mut reloadable = true;
-                if reload.len() > 0 {
-                    reloadable = reload[0];
-                }
-
-                if res.is_err() {
-                    println!("{}", res.as_ref().err().unwrap());
+                if let Err(err) = res {
+                    return Err(NvmError::Other(err));
                 } else if reloadable {
                     let reload = vec![false];
-                    self.use_node(&version, &cpu_arch, &reload);
-                    return;
+                    return self.use_node(&version, a.name(), &reload);
                 }
             } else {
-                println!("{}", err);
-                return;
+                return Err(NvmError::Other(err.to_string()));
             }
         }
 
-        if !ok {
-            return;
-        }
-
         // Use the assigned CPu architechture
-        let cpu_arch = arch::validate(&cpu_arch);
+        let cpu_arch = a.bits();
         let node_path = filepath::join(&self.root, vec![&v_version, "node.exe"]);
         let node32_path = filepath::join(&self.root, vec![&v_version, "node32.exe"]);
         let node64_path = filepath::join(&self.root, vec![&v_version, "node64.exe"]);
@@ -1474,7 +2703,7 @@ impl Environment {
             if node_exists {
                 let _ = fs::rename(&node_path, &node64_path);
             }
-            let _ = fs::rename(&node32_path, &node32_path);
+            let _ = fs::rename(&node32_path, &node_path);
         }
         if node64_exists && cpu_arch == "64" {
             if node_exists {
@@ -1482,151 +2711,73 @@ impl Environment {
             }
             let _ = fs::rename(node64_path, &node_path);
         }
-        println!("Now using node v{} ({}-bit)", version, cpu_arch);
+        println!("Now using node v{} ({})", version, a);
+        // use works offline, the schedule is only read from the cache
+        self.warn_eol(&version, &schedule::cached(&self.root).unwrap_or_default());
+        Ok(())
     }
 
-    #[cfg(target_os = "macos")]
-    fn use_node(&self, version: &str, cpu_arch: &str, reload: &Vec<bool>) {
+    #[cfg(not(target_os = "windows"))]
+    fn use_node(&self, version: &str, cpu_arch: &str, _reload: &Vec<bool>) -> Result<()> {
         let local_install_only = vec![true];
-        let res = self.get_version(version, cpu_arch, &local_install_only);
-
-        if res.is_err() {
-            let err = res.as_ref().err().unwrap();
-            if err.contains("No Major.Minor.Patch") {
-                println!("{}", err);
-                return;
-            }
-        }
-        let (version, cpu_arch) = res.unwrap();
+        let (version, arches) = self.get_version(version, cpu_arch, &local_install_only)?;
+        let a = arches[0];
 
-        if !node::is_version_installed(&self.root, &version, &cpu_arch) {
-            println!("node v{} ({}-bit) is not installed.", &version, &cpu_arch);
-            if cpu_arch == "32" {
-                if node::is_version_installed(&self.root, &version, "64") {
-                    println!("\n Did you mean node v{} (64-bit)?\n If so, type \"nvm use {} 64\" to use it.",&version,&version);
-                }
-            }
-            if cpu_arch == "64" {
-                if node::is_version_installed(&self.root, &version, &cpu_arch) {
-                    println!("\n Did you mean node v{} (32-bit)?\n If so, type \"nvm use {} 32\" to use it.",&version,&version);
-                }
-            }
-            return;
+        if !node::is_version_installed(&self.root, &version, a) {
+            return Err(self.not_installed(&version, a));
         }
 
+        // each architecture has its own dir, so switching is just the link
         let symlink = filepath::clean(&self.symlink);
-        // Remove symlink if it already exists
-        if file::exists(&self.symlink) {
-            let res = fs::remove_file(&symlink);
-            if res.is_err() {
-                if Self::access_denied(&res.as_ref().err().unwrap().to_string()) {
-                    return;
-                }
-            }
-        }
-
-        let mut ok = true;
-        let v_version = format!("v{}", version);
-        let target_symlink = filepath::join(&self.root, vec![&v_version]);
-        let res = std::os::unix::fs::symlink(&target_symlink, &symlink);
-
-        if res.is_err() {
-            let err = res.as_ref().err().unwrap().to_string();
-            if err.contains("not have sufficient privilege")
-                || err.to_lowercase().contains("access is denied")
-            {
-                let res = std::os::unix::fs::symlink(&target_symlink, &symlink);
-
-                if res.is_err() {
-                    ok = false;
-                    println!("{}", err)
-                }
-            } else if err.contains("file already exists") {
-                let res = fs::remove_file(&symlink);
-                let mut reloadable = true;
-                if reload.len() > 0 {
-                    reloadable = reload[0];
-                }
-
-                if res.is_err() {
-                    println!("{}", res.as_ref().err().unwrap());
-                } else if reloadable {
-                    let reload = vec![false];
-                    self.use_node(&version, &cpu_arch, &reload);
-                    return;
-                }
-            } else {
-                println!("{}", err);
-            }
-        }
-
-        if !ok {
-            return;
-        }
-
-        // Use the assigned CPu architechture
-        let cpu_arch = arch::validate(&cpu_arch);
-        let node_path = filepath::join(&self.root, vec![&v_version, "node.exe"]);
-        let node32_path = filepath::join(&self.root, vec![&v_version, "node32.exe"]);
-        let node64_path = filepath::join(&self.root, vec![&v_version, "node64.exe"]);
-        let node_exists = file::exists(&node_path);
-        let node32_exists = file::exists(&node32_path);
-        let node64_exists = file::exists(&node64_path);
-
-        if node32_exists && cpu_arch == "32" {
-            if node_exists {
-                let _ = fs::rename(&node_path, &node64_path);
-            }
-            let _ = fs::rename(&node32_path, &node32_path);
-        }
-        if node64_exists && cpu_arch == "64" {
-            if node_exists {
-                let _ = fs::rename(&node_path, &node32_path);
-            }
-            let _ = fs::rename(node64_path, &node_path);
-        }
-        println!("Now using node v{} ({}-bit)", version, cpu_arch);
+        let version_dir = filepath::join(&self.root, vec![&format!("v{}", version)]);
+        link::switch(&symlink, &node::arch_dir(&version_dir, a))?;
+        println!("Now using node v{} ({})", version, a);
+        // use works offline, the schedule is only read from the cache
+        self.warn_eol(&version, &schedule::cached(&self.root).unwrap_or_default());
+        Ok(())
     }
 
     #[warn(dead_code)]
-    fn use_architecture(&mut self, a: &str) {
-        let processor_architecture = env::var("PROCESSOR_ARCHITECTURE").unwrap();
+    fn use_architecture(&mut self, a: &str) -> Result<()> {
+        let processor_architecture = env::var("PROCESSOR_ARCHITECTURE").unwrap_or_default();
         if strings::contains_any("32", &processor_architecture) {
             println!("This computer only supports 32-bit processing.");
-            return;
+            return Ok(());
         }
         if a == "32" || a == "64" {
-            self.root = a.to_string();
-            self.save_settings();
+            self.arch = a.to_string();
+            self.save_setting("arch", a)?;
             println!("Set to {}-bit mode", a);
+            Ok(())
         } else {
-            println!(
+            Err(NvmError::Usage(format!(
                 "Cannot set architecture to {}. Must be 32 or 64 are accpetable values.",
                 a
-            );
+            )))
         }
     }
 
-    fn list(&self, mut list_type: &str) {
+    fn list(&self, mut list_type: &str) -> Result<()> {
         if list_type.is_empty() {
             list_type = "installed";
         }
 
         if list_type != "installed" && list_type != "available" {
-            println!("\nInvalid list option.\n\nPlease use one of the following:\n - nvm list \n - nvm list installed\n - nvm list available");
-            help();
-            return;
+            return Err(NvmError::Usage("Invalid list option.\n\nPlease use one of the following:\n - nvm list \n - nvm list installed\n - nvm list available".to_string()));
         }
 
         let v_re = Regex::new("v").unwrap();
         if list_type == "installed" {
             println!();
             let (in_use, arch) = node::get_current_version();
-            let installed_versions = node::get_installed(&self.root);
+            let installed_versions = node::get_installed(&self.root)?;
+            // only the cached schedule, list does not wait on the network
+            let lines = schedule::cached(&self.root).unwrap_or_default();
+            let today = chrono::Local::now().date_naive();
 
             if installed_versions.is_empty() {
                 println!("No installations recognized.");
-                return;
+                return Ok(());
             }
 
             for version in &installed_versions {
@@ -1638,17 +2789,31 @@ impl Environment {
                         format!("  {}", v_re.replace_all(version, ""))
                     };
 
+                    // builds of other architectures installed next to it
+                    let arches = node::installed_arches(&self.root, &version[1..]);
+                    if arches.len() > 1 {
+                        let names: Vec<&str> = arches.iter().map(|a| a.name()).collect();
+                        display_version = format!("{} [{}]", display_version, names.join(", "));
+                    }
+                    let phase = Version::parse(&version[1..])
+                        .ok()
+                        .and_then(|v| schedule::find(&lines, &v))
+                        .map(|l| l.phase(today));
+                    if let Some(
+                        phase @ (schedule::Phase::Maintenance | schedule::Phase::EndOfLife),
+                    ) = phase
+                    {
+                        display_version = format!("{} ({})", display_version, phase.name());
+                    }
                     if v_in_use == *version {
-                        display_version = format!(
-                            "{} (Currently using {}-bit executable)",
-                            display_version, arch
-                        );
+                        display_version =
+                            format!("{} (Currently using {} executable)", display_version, arch);
                     }
                     println!("{}\n", display_version);
                 }
             }
         } else {
-            let (_, lts, current, stable, unstable, _) = node::get_available(&self.ctx.web_ctx);
+            let (_, lts, current, stable, unstable, _) = node::get_available(&self.ctx.web_ctx)?;
             let releases = 20;
             let mut data = vec![vec![Version::new(0, 0, 0); 4]; releases];
 
@@ -1678,28 +2843,48 @@ impl Environment {
                 TableCell::new_with_alignment("Old UnStable", 1, Alignment::Center),
             ]));
 
+            // lines in maintenance get a *, those past their end of life a !,
+            // when a schedule has been cached
+            let lines = schedule::cached(&self.root).unwrap_or_default();
+            let today = chrono::Local::now().date_naive();
+            let cell = |v: &Version| {
+                let mark = match schedule::find(&lines, v).map(|l| l.phase(today)) {
+                    Some(schedule::Phase::Maintenance) => " *",
+                    Some(schedule::Phase::EndOfLife) => " !",
+                    _ => "",
+                };
+                TableCell::builder(format!("{}{}", v, mark))
+                    .col_span(1)
+                    .alignment(Alignment::Center)
+                    .build()
+            };
             for release in data {
                 table.add_row(Row::new(vec![
-                    TableCell::new_with_alignment(release[0].to_string(), 1, Alignment::Center),
-                    TableCell::new_with_alignment(release[1].to_string(), 1, Alignment::Center),
-                    TableCell::new_with_alignment(release[2].to_string(), 1, Alignment::Center),
-                    TableCell::new_with_alignment(release[3].to_string(), 1, Alignment::Center),
+                    cell(&release[0]),
+                    cell(&release[1]),
+                    cell(&release[2]),
+                    cell(&release[3]),
                 ]));
             }
             println!("{}", table.render());
+            if !lines.is_empty() {
+                println!("* maintenance, critical fixes only    ! end-of-life, no security fixes");
+            }
         }
+        Ok(())
     }
 
-    fn enable(&self) {
+    fn enable(&self) -> Result<()> {
         let mut dir = String::new();
-        let files = fs::read_dir(&self.root).unwrap();
+        let files = fs::read_dir(&self.root)
+            .map_err(|err| NvmError::io(&format!("Read {}", self.root), err))?;
 
         for t in files {
-            let f = t.unwrap();
-            let fm = f.metadata().unwrap();
+            let f = t?;
+            let fm = f.metadata()?;
 
             if fm.is_dir() {
-                let filename = f.file_name().into_string().unwrap();
+                let filename = f.file_name().to_string_lossy().to_string();
                 if filename.contains("v") {
                     dir = filename;
                 }
@@ -1709,56 +2894,44 @@ impl Environment {
         let trim_c: &[_] = &['\r', '\n'];
         dir = dir.trim_matches(trim_c).to_string();
         if dir.is_empty() {
-            println!("No version of node.js found. Try installing the latest by typing nvm install latest");
+            Err(NvmError::NotFound("No version of node.js found. Try installing the latest by typing nvm install latest".to_string()))
         } else {
             let re = Regex::new("v").unwrap();
             let version = re.replace_all(&dir, "");
             let reload = vec![];
-            self.use_node(version.as_ref(), &self.arch, &reload);
+            self.use_node(version.as_ref(), &self.arch, &reload)
         }
     }
 
     #[cfg(target_os = "windows")]
-    fn disable(&self) {
+    fn disable(&self) -> Result<()> {
         let symlink = filepath::clean(&self.symlink);
         match cmd::elevated_run(&self.root, "rmdir", vec![&symlink]) {
-            Ok(ok) => {
-                if ok {
-                    println!("nvm disabled successful");
-                } else {
-                    println!("nvm disabled fail");
-                }
-            }
-            Err(err) => {
-                println!("{}", err);
+            Ok(true) => {
+                println!("nvm disabled successful");
+                Ok(())
             }
+            Ok(false) => Err(NvmError::Other("nvm disabled fail".to_string())),
+            Err(err) => Err(NvmError::Other(err)),
         }
     }
 
     #[cfg(target_os = "linux")]
-    fn disable(&self) {
+    fn disable(&self) -> Result<()> {
         let symlink = filepath::clean(&self.symlink);
-        match fs::remove_file(&symlink) {
-            Ok(_) => {
-                println!("nvm disabled successful");
-            }
-            Err(err) => {
-                println!("{:?}", err);
-            }
-        }
+        fs::remove_file(&symlink)
+            .map_err(|err| NvmError::io(&format!("Remove {}", symlink), err))?;
+        println!("nvm disabled successful");
+        Ok(())
     }
 
     #[cfg(target_os = "macos")]
-    fn disable(&self) {
+    fn disable(&self) -> Result<()> {
         let symlink = filepath::clean(&self.symlink);
-        match fs::remove_file(&symlink) {
-            Ok(_) => {
-                println!("nvm disabled successful");
-            }
-            Err(err) => {
-                println!("{:?}", err);
-            }
-        }
+        fs::remove_file(&symlink)
+            .map_err(|err| NvmError::io(&format!("Remove {}", symlink), err))?;
+        println!("nvm disabled successful");
+        Ok(())
     }
 
     #[warn(dead_code)]
@@ -1769,29 +2942,51 @@ impl Environment {
         Ok((admin, elevated))
     }
 
-    fn setup(&mut self) {
-        let lines = file::read_lines(self.settings.as_str());
-        if lines.is_err() {
-            println!("ERROR:{}", lines.err().unwrap());
-            exit(1);
+    // the settings of the layers, where user mode keeps versions and the
+    // active link under $XDG_DATA_HOME/nvm-rs unless they are set
+    fn resolve_settings(layers: &Layers) -> Settings {
+        let mut settings = layers.settings();
+        if layers.user_mode() {
+            if let Some(data_dir) = config::user_data_dir() {
+                if settings.root.is_empty() {
+                    settings.root = filepath::join(data_dir.to_str().unwrap(), vec!["versions"]);
+                }
+                if settings.symlink.is_empty() {
+                    settings.symlink = filepath::join(data_dir.to_str().unwrap(), vec!["current"]);
+                }
+            }
         }
-        let lines = lines.unwrap();
-        let mut m = HashMap::new();
-        for line in lines {
-            let line = line.trim();
-            strings::replace_env_vars(line);
+        settings
+    }
 
-            let res = line.split(":").collect::<Vec<&str>>();
-            if res.len() < 2 {
-                continue;
-            }
-            m.insert(res[0].to_string(), res[1..].join(":").trim().to_string());
+    // config runs without setup so that broken settings can still be fixed,
+    // the root to lock is read from the settings as they are
+    fn config_root(&self) -> String {
+        Layers::load(&self.settings, &self.overrides)
+            .map(|layers| Self::resolve_settings(&layers).root)
+            .unwrap_or_default()
+    }
+
+    fn setup(&mut self) -> Result<()> {
+        let layers = Layers::load(self.settings.as_str(), &self.overrides)
+            .map_err(|err| NvmError::from(err).context("Read settings"))?;
+        self.user_mode = layers.user_mode();
+        if !self.user_mode && !layers.has_system() && layers.get("root").is_none() {
+            return Err(NvmError::Config(format!(
+                "settings file {} not found",
+                self.settings
+            )));
+        }
+        let settings = Self::resolve_settings(&layers);
+        if self.user_mode && !settings.root.is_empty() && !file::exists(&settings.root) {
+            fs::create_dir_all(&settings.root)
+                .map_err(|err| NvmError::io(&format!("Create {}", settings.root), err))?;
         }
 
-        if let Some(root) = m.get("root") {
-            self.root = PathBuf::from(root)
+        if !settings.root.is_empty() {
+            self.root = PathBuf::from(&settings.root)
                 .canonicalize()
-                .unwrap()
+                .unwrap_or_else(|_| PathBuf::from(&settings.root))
                 .to_string_lossy()
                 .to_string();
 
@@ -1801,60 +2996,72 @@ impl Environment {
             }
         }
 
-        if let Some(symlink) = m.get("symlink") {
-            self.symlink = symlink.to_string();
-        }
-
-        if let Some(original_path) = m.get("originalpath") {
-            self.original_path = PathBuf::from(original_path)
-                .canonicalize()
-                .unwrap()
-                .to_string_lossy()
-                .to_string()
+        if !settings.symlink.is_empty() {
+            self.symlink = settings.symlink;
         }
 
-        if let Some(original_version) = m.get("originalversion") {
-            self.original_version = original_version.to_string();
+        if !settings.original_path.is_empty() {
+            self.original_path = settings.original_path;
         }
 
-        if let Some(arch) = m.get("arch") {
-            self.arch = arch.to_string();
+        if !settings.original_version.is_empty() {
+            self.original_version = settings.original_version;
         }
 
-        if let Some(node_mirror) = m.get("node_mirror") {
-            self.node_mirror = node_mirror.to_string();
+        if !settings.arch.is_empty() {
+            self.arch = settings.arch;
         }
 
-        if let Some(npm_mirror) = m.get("npm_mirror") {
-            self.npm_mirror = npm_mirror.to_string();
-        }
+        self.node_mirror = settings.node_mirror;
+        self.npm_mirror = settings.npm_mirror;
 
-        if let Some(proxy) = m.get("proxy") {
-            if proxy != "none" && !proxy.is_empty() {
-                if proxy.to_lowercase().chars().take(4).collect::<String>() != "http" {
-                    let mut tmp = "http://".to_string();
-                    tmp.push_str(proxy.as_str());
-                    self.proxy = tmp;
-                }
+        self.no_proxy = settings.no_proxy;
+        self.ca_file = settings.ca_file;
+        self.ca_dir = settings.ca_dir;
+        self.ca_system = settings.ca_system == "true";
+        self.corepack = settings.corepack == "enable";
+        if !settings.proxy.is_empty() {
+            match config::validate("proxy", &settings.proxy) {
+                Ok(proxy) => self.proxy = proxy,
+                Err(err) => println!("{}", err),
             }
         }
 
-        if let Err(err) = self
-            .ctx
+        self.ctx
+            .web_ctx
+            .set_mirrors(&self.node_mirror, &self.npm_mirror);
+        self.ctx
             .web_ctx
-            .set_proxy(self.proxy.as_str(), self.verify_ssl)
+            .set_archive_format(&settings.archive_format);
+        #[cfg(target_os = "linux")]
         {
-            println!("Set proxy fail,err:{}", err);
-        }
-        self.arch = arch::validate(&self.arch);
+            let libc = match settings.libc.as_str() {
+                "" | "auto" => arch::libc().to_string(),
+                libc => config::validate("libc", libc).map_err(NvmError::Config)?,
+            };
+            self.ctx.web_ctx.set_libc(&libc);
+        }
+
+        let tls = TlsOptions {
+            verify_ssl: self.verify_ssl,
+            ca_file: self.ca_file.clone(),
+            ca_dir: self.ca_dir.clone(),
+            ca_system: self.ca_system,
+        };
+        self.ctx
+            .web_ctx
+            .set_proxy(self.proxy.as_str(), &self.no_proxy, &tls)?;
+        self.arch = Arch::parse(&self.arch)
+            .unwrap_or_else(Arch::host)
+            .to_string();
 
         if !PathBuf::from(&self.root).exists() {
-            println!(
+            return Err(NvmError::NotFound(format!(
                 "{} could not be found or does not exist. Exiting.",
                 self.root
-            );
-            exit(1);
+            )));
         }
+        Ok(())
     }
 }
 
@@ -1871,7 +3078,7 @@ fn test_get_version() {
     let env = Environment::new();
     let local_only = vec![false];
     let (v, c) = env.get_version("14.16.0", "64", &local_only).unwrap();
-    println!("version:{},cpu:{}", v, c);
+    println!("version:{},cpu:{:?}", v, c);
 }
 
 #[test]
@@ -1907,5 +3114,32 @@ fn test_install() {
     let mut path = env::current_dir().unwrap();
     path.push("tmp");
     env.root = path.to_str().unwrap().to_string();
-    env.install("18.20.1", "64");
+    let _ = env.install("18.20.1", "64");
+}
+
+#[test]
+#[cfg(test)]
+fn test_config_set_lock() {
+    let dir = env::temp_dir().join(format!("nvm-config-lock-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let root = dir.to_string_lossy().to_string();
+    let settings = filepath::join(&root, vec!["settings.txt"]);
+    fs::write(&settings, format!("root: {}\n", root)).unwrap();
+
+    let mut env = Environment::new();
+    env.settings = settings.clone();
+    env.no_wait = true;
+    let args: Vec<String> = ["nvm", "config", "set", "arch", "arm64", "--system"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect();
+    let held = lock::acquire(&root, lock::Mode::Exclusive, false).unwrap();
+    assert!(matches!(run(&mut env, &args), Err(NvmError::Busy(_))));
+    assert!(!fs::read_to_string(&settings).unwrap().contains("arm64"));
+    drop(held);
+    run(&mut env, &args).unwrap();
+    assert!(fs::read_to_string(&settings).unwrap().contains("arm64"));
+
+    fs::remove_dir_all(&dir).unwrap();
 }